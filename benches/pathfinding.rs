@@ -20,7 +20,16 @@ fn setup() -> World {
         will: 3,
     };
     let challenge = Challenge::default();
-    World::new(seed, WORLD_SIZE.x, 32, player_info, challenge)
+    let regions = false;
+    World::new(
+        seed,
+        WORLD_SIZE.x,
+        32,
+        player_info,
+        challenge,
+        Default::default(),
+        regions,
+    )
 }
 
 // TODO: actually, maybe we don't care about nearest dose because it's not used for monsters
@@ -8,7 +8,9 @@ with open(sys.argv[1], 'r') as i:
     for line in i:
         if len(line) > 0 and line[0] == '{':
             j = json.loads(line)
-            del j["verification"]
+            # NOTE: the replay header is a JSON object too, but it
+            # doesn't have any verifications.
+            j.pop("verification", None)
             filtered_result = json.dumps(j)
         else:
             filtered_result = line.strip()
@@ -195,6 +195,11 @@ fn main() {
             }
         }
     }
+    // NOTE: the terminal backend only builds on Unix. The feature can
+    // still be on elsewhere (e.g. with `prod`).
+    if std::env::var_os("CARGO_CFG_UNIX").is_none() {
+        backends.retain(|backend| backend != "terminal");
+    }
 
     writeln!(
         lookup_table_contents,
@@ -0,0 +1,70 @@
+use crate::{
+    session::{GameSession, Observation, StepResult},
+    state::Command,
+};
+
+use std::{
+    error::Error,
+    io::{BufRead, Write},
+};
+
+use serde::Serialize;
+
+/// The line the game writes in response to every line it reads (and
+/// once at the start).
+#[derive(Serialize)]
+struct Response {
+    /// What happened after the command. `None` at the start and when
+    /// the line wasn't a valid command.
+    result: Option<StepResult>,
+    /// Why the line couldn't be read as a command.
+    error: Option<String>,
+    observation: Observation,
+}
+
+/// Play the game by reading commands from `input` and writing what
+/// the player sees into `output`, both as JSON, one per line.
+///
+/// The commands are the same as in the command replays: `"N"`,
+/// `"SE"`, `"UseFood"`, etc. The game stops when the input ends.
+pub fn run(
+    mut session: GameSession,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    respond(&mut output, None, None, &session)?;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Command>(&line) {
+            Ok(command) => {
+                let result = session.step(command);
+                respond(&mut output, Some(result), None, &session)?;
+            }
+            Err(err) => {
+                log::warn!("Invalid command: `{}`: {}", line, err);
+                respond(&mut output, None, Some(err.to_string()), &session)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn respond(
+    output: &mut impl Write,
+    result: Option<StepResult>,
+    error: Option<String>,
+    session: &GameSession,
+) -> Result<(), Box<dyn Error>> {
+    let response = Response {
+        result,
+        error,
+        observation: session.observation(),
+    };
+    serde_json::to_writer(&mut *output, &response)?;
+    writeln!(output)?;
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,275 @@
+use crate::{
+    blocker::Blocker,
+    formula, item,
+    level::TileKind,
+    monster,
+    pathfinding::{self, Path},
+    player::Mind,
+    point::Point,
+    state::{Command, State},
+};
+
+/// How far from the player the bot looks for things to do.
+const SEARCH_RADIUS: i32 = 12;
+
+/// Eat or look for a dose once the Withdrawal drops this low.
+const CRAVING_WITHDRAWAL: i32 = 5;
+
+/// Don't bother picking up more food than this.
+const MAX_FOOD: usize = 3;
+
+const DIRECTIONS: [(Point, Command); 8] = [
+    (Point { x: 0, y: -1 }, Command::N),
+    (Point { x: 1, y: -1 }, Command::NE),
+    (Point { x: 1, y: 0 }, Command::E),
+    (Point { x: 1, y: 1 }, Command::SE),
+    (Point { x: 0, y: 1 }, Command::S),
+    (Point { x: -1, y: 1 }, Command::SW),
+    (Point { x: -1, y: 0 }, Command::W),
+    (Point { x: -1, y: -1 }, Command::NW),
+];
+
+/// Pick the player's next command.
+///
+/// The bot doesn't remember anything between the commands so it
+/// always does the same thing in the same situation. It eats when
+/// the Withdrawal gets low, looks for a dose when it runs out of
+/// food, fights the monsters next to it, bumps into Anxieties to
+/// raise its Will and heads for the Victory NPC once it shows up.
+/// Otherwise, it explores.
+pub fn next_command(state: &State) -> Command {
+    let player = &state.player;
+    let in_withdrawal = matches!(player.mind, Mind::Withdrawal(_));
+    let craving = match player.mind {
+        Mind::Withdrawal(value) => value.to_int() <= CRAVING_WITHDRAWAL,
+        Mind::Sober(_) | Mind::High(_) => false,
+    };
+    let food_count = player
+        .inventory
+        .iter()
+        .filter(|item| item.kind == item::Kind::Food)
+        .count();
+
+    if craving && food_count > 0 {
+        return Command::UseFood;
+    }
+
+    // NOTE: monsters next to the player attack every turn and
+    // bumping into them gets rid of them. Depression kills on touch
+    // so it goes first.
+    let adjacent_monster = DIRECTIONS
+        .iter()
+        .filter_map(|(offset, command)| {
+            let kind = monster_at(state, player.pos + *offset)?;
+            kind.is_monster().then_some((kind, command))
+        })
+        .min_by_key(|(kind, _)| *kind != monster::Kind::Depression);
+    match adjacent_monster {
+        Some((monster::Kind::Depression, command)) => return command.clone(),
+        Some((_, command)) => return safe(state, command.clone(), !in_withdrawal),
+        None => {}
+    }
+
+    let command = pick_destination(state, in_withdrawal, food_count);
+    safe(state, command, !in_withdrawal)
+}
+
+/// Where the bot wants to go when there's nothing to fight.
+fn pick_destination(state: &State, in_withdrawal: bool, food_count: usize) -> Command {
+    let player = &state.player;
+
+    if in_withdrawal {
+        if let Some((dose_pos, _)) = state.world.nearest_dose(player.pos, SEARCH_RADIUS) {
+            if let Some(command) = step_towards(state, dose_pos) {
+                return command;
+            }
+        }
+    }
+
+    if player.mind.is_sober() {
+        let victory_npc_pos = state
+            .victory_npc_id
+            .and_then(|id| state.world.monster(id))
+            .map(|npc| npc.position);
+        if let Some(command) = victory_npc_pos.and_then(|pos| step_towards(state, pos)) {
+            return command;
+        }
+    }
+
+    if food_count < MAX_FOOD {
+        let food = nearest(state, |pos| {
+            state
+                .world
+                .cell(pos)
+                .is_some_and(|cell| cell.items.iter().any(|item| item.kind == item::Kind::Food))
+        });
+        if let Some(command) = food.and_then(|pos| step_towards(state, pos)) {
+            return command;
+        }
+    }
+
+    if player.mind.is_sober() {
+        let anxiety = nearest(state, |pos| {
+            monster_at(state, pos) == Some(monster::Kind::Anxiety)
+        });
+        if let Some(command) = anxiety.and_then(|pos| step_towards(state, pos)) {
+            return command;
+        }
+    }
+
+    explore(state)
+}
+
+/// Don't end up anywhere a Depression can reach and attack before
+/// the player can act again (or where it can get close enough to do
+/// it next turn, no matter where the player goes). Unless
+/// `avoid_doses` is off, don't get close enough to a dose for it to
+/// pull the player in either. If every way is dangerous, go ahead
+/// anyway.
+fn safe(state: &State, command: Command, avoid_doses: bool) -> Command {
+    let player_pos = state.player.pos;
+    let offset = match DIRECTIONS.iter().find(|(_, c)| *c == command) {
+        Some((offset, _)) => *offset,
+        None => return command,
+    };
+    let depressions: Vec<Point> = state
+        .world
+        .monsters(formula::simulation_area(player_pos))
+        .filter(|monster| !monster.dead && monster.kind == monster::Kind::Depression)
+        .map(|monster| monster.position)
+        .collect();
+    let reach = formula::depression_max_ap(state.challenge);
+    let will = state.player.will.to_int();
+    let dangerous = |pos: Point| {
+        let irresistible_dose = avoid_doses
+            && state
+                .world
+                .nearest_dose(pos, 5)
+                .is_some_and(|(dose_pos, dose)| {
+                    pos.tile_distance(dose_pos)
+                        < formula::player_resist_radius(dose.irresistible, will)
+                });
+        irresistible_dose
+            || depressions.iter().any(|&depression| {
+                let distance = pos.tile_distance(depression);
+                distance <= reach * 2
+            })
+    };
+
+    // NOTE: bumping into a monster leaves the player where they are.
+    let destination = player_pos + offset;
+    let end_pos = if monster_at(state, destination).is_some() {
+        player_pos
+    } else {
+        destination
+    };
+    if !dangerous(end_pos) {
+        return command;
+    }
+    DIRECTIONS
+        .iter()
+        .map(|(offset, command)| (player_pos + *offset, command))
+        .filter(|&(pos, _)| {
+            !dangerous(pos)
+                && monster_at(state, pos).is_none()
+                && state.world.walkable(pos, Blocker::WALL, player_pos)
+        })
+        .min_by_key(|&(pos, _)| pos.tile_distance(destination))
+        .map_or(command, |(_, command)| command.clone())
+}
+
+fn monster_at(state: &State, pos: Point) -> Option<monster::Kind> {
+    state
+        .world
+        .monsters(formula::simulation_area(state.player.pos))
+        .find(|monster| monster.position == pos && !monster.dead)
+        .map(|monster| monster.kind)
+}
+
+/// The closest visible position that matches the predicate.
+fn nearest(state: &State, predicate: impl Fn(Point) -> bool) -> Option<Point> {
+    let player_pos = state.player.pos;
+    let radius = formula::exploration_radius(state.player.mind);
+    let mut result: Option<Point> = None;
+    for y in -radius..=radius {
+        for x in -radius..=radius {
+            let pos = player_pos + (x, y);
+            if pos == player_pos || !pos.inside_circular_area(player_pos, radius) {
+                continue;
+            }
+            let closer = result
+                .is_none_or(|best| player_pos.tile_distance(pos) < player_pos.tile_distance(best));
+            if closer && predicate(pos) {
+                result = Some(pos);
+            }
+        }
+    }
+    result
+}
+
+fn step_towards(state: &State, destination: Point) -> Option<Command> {
+    let player = &state.player;
+    let check_irresistible = true;
+    let mut path = Path::find(
+        player.pos,
+        destination,
+        &state.world,
+        Blocker::WALL,
+        player.pos,
+        player.will.to_int(),
+        check_irresistible,
+        formula::PATHFINDING_PLAYER_MOUSE_LIMIT,
+        &pathfinding::player_cost,
+    );
+    let next = path.next()?;
+    command_from_offset(next - player.pos)
+}
+
+fn command_from_offset(offset: Point) -> Option<Command> {
+    DIRECTIONS
+        .iter()
+        .find(|(direction, _)| *direction == offset)
+        .map(|(_, command)| command.clone())
+}
+
+/// Head for the unexplored places. The general direction depends on
+/// the seed and it changes every hundred turns so the bot doesn't
+/// get stuck in a corner forever.
+fn explore(state: &State) -> Command {
+    let player_pos = state.player.pos;
+    let angle = ((state.seed % 360) as f32 + (state.turn / 100) as f32 * 90.0).to_radians();
+    let heading = (angle.cos(), angle.sin());
+    let score = |offset: Point| {
+        offset.x as f32 * heading.0 + offset.y as f32 * heading.1
+            - player_pos.distance(player_pos + offset) * 0.5
+    };
+
+    let mut best: Option<(f32, Point)> = None;
+    for y in -SEARCH_RADIUS..=SEARCH_RADIUS {
+        for x in -SEARCH_RADIUS..=SEARCH_RADIUS {
+            let offset = Point::new(x, y);
+            let unexplored = state
+                .world
+                .cell(player_pos + offset)
+                .is_some_and(|cell| !cell.explored && cell.tile.kind != TileKind::Tree);
+            if unexplored && best.is_none_or(|(best_score, _)| score(offset) > best_score) {
+                best = Some((score(offset), player_pos + offset));
+            }
+        }
+    }
+    if let Some(command) = best.and_then(|(_, pos)| step_towards(state, pos)) {
+        return command;
+    }
+
+    // NOTE: there's nothing to explore (or we can't get there), just
+    // go wherever we can.
+    DIRECTIONS
+        .iter()
+        .filter(|(offset, _)| {
+            state
+                .world
+                .walkable(player_pos + *offset, Blocker::WALL, player_pos)
+        })
+        .max_by(|(a, _), (b, _)| score(*a).total_cmp(&score(*b)))
+        .map_or(Command::N, |(_, command)| command.clone())
+}
@@ -0,0 +1,248 @@
+use crate::{
+    autoplay, item,
+    player::CauseOfDeath,
+    random::Random,
+    report::Report,
+    session::GameSession,
+    state::{Challenge, Command, State},
+};
+
+use std::{collections::BTreeMap, error::Error, fmt, io::Write, str::FromStr, thread};
+
+use serde::Serialize;
+
+/// Who plays the games.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Policy {
+    /// The `autoplay` bot.
+    Autoplay,
+    /// Walk in a random direction every turn.
+    RandomWalk,
+}
+
+pub const POLICY_NAMES: &[&str] = &["autoplay", "random-walk"];
+
+impl FromStr for Policy {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let policy = match s {
+            "autoplay" => Policy::Autoplay,
+            "random-walk" => Policy::RandomWalk,
+            _ => throw!(&format!(
+                "Unknown policy: `{}`. Expected one of: {}",
+                s,
+                POLICY_NAMES.join(", ")
+            )),
+        };
+        Ok(policy)
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Policy::Autoplay => f.write_str("autoplay"),
+            Policy::RandomWalk => f.write_str("random-walk"),
+        }
+    }
+}
+
+/// How a game played by the policy ended.
+#[derive(Clone, Debug)]
+pub struct Game {
+    pub report: Report,
+    /// The policy couldn't finish the game. See `GameSession::play`.
+    pub stuck: bool,
+}
+
+impl Game {
+    pub fn doses_used(&self) -> i32 {
+        self.report
+            .items_used
+            .iter()
+            .filter(|(&kind, _)| kind != item::Kind::Food)
+            .map(|(_, count)| count)
+            .sum()
+    }
+}
+
+/// The smallest, largest, average and middle value of a number
+/// measured in every game.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Distribution {
+    pub min: i32,
+    pub max: i32,
+    pub mean: f64,
+    pub median: f64,
+}
+
+impl Distribution {
+    fn new(mut values: Vec<i32>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_unstable();
+        let count = values.len();
+        let median = if count.is_multiple_of(2) {
+            f64::from(values[count / 2 - 1] + values[count / 2]) / 2.0
+        } else {
+            f64::from(values[count / 2])
+        };
+        Self {
+            min: values[0],
+            max: values[count - 1],
+            mean: values.iter().map(|&value| f64::from(value)).sum::<f64>() / count as f64,
+            median,
+        }
+    }
+}
+
+/// The numbers to compare between balance changes.
+#[derive(Clone, Debug, Serialize)]
+pub struct Summary {
+    pub policy: Policy,
+    pub challenge: Challenge,
+    pub first_seed: u32,
+    pub games: usize,
+    pub victories: usize,
+    pub victory_rate: f64,
+    /// The games the policy couldn't finish. They're counted in
+    /// everything else as they ended up.
+    pub stuck: usize,
+    pub turns: Distribution,
+    /// The turns of the games the player didn't win.
+    pub survival_turns: Distribution,
+    pub causes_of_death: BTreeMap<CauseOfDeath, usize>,
+    pub doses_used: Distribution,
+    /// All the items used in all the games.
+    pub items_used: BTreeMap<item::Kind, i32>,
+}
+
+impl Summary {
+    pub fn new(policy: Policy, challenge: Challenge, first_seed: u32, games: &[Game]) -> Self {
+        let victories = games.iter().filter(|game| game.report.victory).count();
+        let mut causes_of_death = BTreeMap::new();
+        let mut items_used = BTreeMap::new();
+        for game in games {
+            if let Some(cause) = game.report.cause_of_death {
+                *causes_of_death.entry(cause).or_insert(0) += 1;
+            }
+            for (&kind, &count) in &game.report.items_used {
+                *items_used.entry(kind).or_insert(0) += count;
+            }
+        }
+        Self {
+            policy,
+            challenge,
+            first_seed,
+            games: games.len(),
+            victories,
+            victory_rate: if games.is_empty() {
+                0.0
+            } else {
+                victories as f64 / games.len() as f64
+            },
+            stuck: games.iter().filter(|game| game.stuck).count(),
+            turns: Distribution::new(games.iter().map(|game| game.report.turns).collect()),
+            survival_turns: Distribution::new(
+                games
+                    .iter()
+                    .filter(|game| !game.report.victory)
+                    .map(|game| game.report.turns)
+                    .collect(),
+            ),
+            causes_of_death,
+            doses_used: Distribution::new(games.iter().map(Game::doses_used).collect()),
+            items_used,
+        }
+    }
+}
+
+/// Play a game for every seed from `first_seed` on. The games run on
+/// all the available cores and they're returned in the order of their
+/// seeds.
+pub fn run(policy: Policy, challenge: Challenge, first_seed: u32, count: u32) -> Vec<Game> {
+    let seeds: Vec<u32> = (0..count).map(|i| first_seed.wrapping_add(i)).collect();
+    let thread_count = thread::available_parallelism().map_or(1, std::num::NonZero::get);
+    let chunk_size = seeds.len().div_ceil(thread_count).max(1);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = seeds
+            .chunks(chunk_size)
+            .map(|seeds| {
+                scope.spawn(move || {
+                    seeds
+                        .iter()
+                        .map(|&seed| play(policy, challenge, seed))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| match handle.join() {
+                Ok(games) => games,
+                Err(panic) => std::panic::resume_unwind(panic),
+            })
+            .collect()
+    })
+}
+
+fn play(policy: Policy, challenge: Challenge, seed: u32) -> Game {
+    let mut session = GameSession::new(seed, challenge);
+    let result = match policy {
+        Policy::Autoplay => session.play(autoplay::next_command),
+        Policy::RandomWalk => {
+            let mut rng = Random::from_seed(seed);
+            session.play(|_state: &State| random_direction(&mut rng))
+        }
+    };
+    if let Err(err) = &result {
+        log::warn!("Seed {}: {}", seed, err);
+    }
+    log::debug!("Seed {} finished at turn {}.", seed, session.state().turn);
+    Game {
+        report: Report::new(session.state()),
+        stuck: result.is_err(),
+    }
+}
+
+fn random_direction(rng: &mut Random) -> Command {
+    use crate::state::Command::*;
+    rng.choose_with_fallback(&[N, NE, E, SE, S, SW, W, NW], &N)
+        .clone()
+}
+
+/// Write one line per game.
+pub fn write_csv(mut writer: impl Write, games: &[Game]) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        writer,
+        "seed,victory,turns,cause_of_death,perpetrator,doses_used,food_used,\
+         turns_in_withdrawal,turns_sober,turns_high,will,stuck"
+    )?;
+    for game in games {
+        let report = &game.report;
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            report.seed,
+            report.victory,
+            report.turns,
+            report
+                .cause_of_death
+                .map_or(String::new(), |cause| format!("{:?}", cause)),
+            report
+                .perpetrator
+                .map_or(String::new(), |kind| format!("{:?}", kind)),
+            game.doses_used(),
+            report.items_used.get(&item::Kind::Food).unwrap_or(&0),
+            report.turns_in_withdrawal,
+            report.turns_sober,
+            report.turns_high,
+            report.will,
+            game.stuck,
+        )?;
+    }
+    Ok(())
+}
@@ -23,6 +23,12 @@ pub mod opengl;
 
 pub mod headless;
 
+#[cfg(all(feature = "terminal-backend", unix))]
+pub mod terminal;
+
+#[cfg(feature = "glutin-backend")]
+mod rasterizer;
+
 pub const DRAWCALL_CAPACITY: usize = 8000;
 pub const VERTEX_CAPACITY: usize = 70_000;
 pub const VERTEX_COMPONENT_COUNT: usize = 9;
@@ -31,6 +31,7 @@ fn key_code_from_backend(backend_code: BackendKey) -> Option<KeyCode> {
         BackendKey::Return => Some(KeyCode::Enter),
         BackendKey::Escape => Some(KeyCode::Esc),
         BackendKey::Space => Some(KeyCode::Space),
+        BackendKey::Back => Some(KeyCode::Backspace),
 
         BackendKey::Key0 => Some(KeyCode::D0),
         BackendKey::Key1 => Some(KeyCode::D1),
@@ -339,6 +340,8 @@ monitor ID: {:?}. Ignoring this request.",
                     loop_state.handle_window_size_changed(logical_size.width, logical_size.height);
                 }
 
+                WindowEvent::Focused(false) => loop_state.handle_focus_lost(),
+
                 WindowEvent::Moved(new_pos) => {
                     if loop_state.settings.fullscreen || loop_state.switched_from_fullscreen {
                         // Don't update the window position
@@ -688,6 +691,9 @@ monitor ID: {:?}. Ignoring this request.",
             // setting the `exiting` bool, it only gets called once.
             exiting = true;
             settings_store.save(&loop_state.settings);
+            // NOTE: the event loop never returns so the session has
+            // to end here rather than in `run`.
+            crate::save::Slots::in_data_dir().end_session();
         }
     });
 }
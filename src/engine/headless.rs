@@ -1,6 +1,11 @@
 use crate::{
     audio::Audio,
-    engine::{loop_state::Metrics, Display, Mouse},
+    engine::{
+        loop_state::{egui_set_font_size, egui_style, Metrics},
+        rasterizer::{self, Rasterizer},
+        Display, Mouse,
+    },
+    error::ReplayError,
     game::RunningState,
     gamepad::Gamepad,
     point::Point,
@@ -8,22 +13,72 @@ use crate::{
     state::State,
 };
 
-use std::time::Duration;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
-use egui::CtxRef;
+use egui::{CtxRef, RawInput};
+
+/// Save every rendered frame as a PNG image.
+///
+/// The files are numbered `img000000.png`, `img000001.png`, etc. so
+/// they can be turned into a video with ffmpeg.
+#[derive(Clone, Debug)]
+pub struct FrameExport {
+    pub dir: PathBuf,
+
+    /// Size of the exported images. The frames are scaled to fit and
+    /// letterboxed. If `None`, they're the size of the game window.
+    pub size_px: Option<Point>,
+}
 
 pub fn main_loop<S>(
+    settings_store: S,
+    initial_state: Box<State>,
+    frame_export: Option<FrameExport>,
+) -> Result<(), ReplayError>
+where
+    S: SettingsStore + 'static,
+{
+    run(settings_store, initial_state, frame_export).map(|_final_state| ())
+}
+
+/// Run the game without any window until it stops and return its
+/// final state. Nothing is rendered unless we're exporting frames.
+///
+/// If the game is a replay that doesn't match what this build is
+/// doing, the error says where and how it went wrong.
+pub fn run<S>(
     mut settings_store: S,
     initial_state: Box<State>,
-) -> Result<(), Box<dyn std::error::Error>>
+    frame_export: Option<FrameExport>,
+) -> Result<Box<State>, ReplayError>
 where
     S: SettingsStore + 'static,
 {
     let mut settings = settings_store.load();
 
+    // NOTE: The mouse positions in the replay log depend on the
+    // display the game was recorded on. Use the one from the replay
+    // header rather than whatever the local settings say.
+    if let Some(header) = &initial_state.replay_header {
+        settings.window_width = header.display.window_size_px.x as u32;
+        settings.window_height = header.display.window_size_px.y as u32;
+        settings.tile_size = header.display.tile_size;
+        settings.text_size = header.display.text_size;
+    }
+
     let mut egui_context = CtxRef::default();
     egui_context.begin_frame(Default::default());
 
+    let mut rasterizer = None;
+    if let Some(frame_export) = &frame_export {
+        std::fs::create_dir_all(&frame_export.dir)?;
+        egui_set_font_size(&egui_context, settings.text_size as f32);
+        rasterizer = Some(Rasterizer::new(initial_state.palette.unexplored_background));
+        // NOTE: every loop iteration will begin its own frame now.
+        let _ = egui_context.end_frame();
+    }
+    let mut frame_id = 0;
+
     let mouse = Mouse::new();
     let keys = vec![];
     let mut gamepad = Gamepad::new();
@@ -44,6 +99,26 @@ where
     let fps = 60;
 
     loop {
+        if rasterizer.is_some() {
+            // NOTE: egui only produces the meshes for the UI at the
+            // end of a frame. We don't need them otherwise.
+            egui_context.begin_frame(RawInput {
+                screen_rect: Some(egui::Rect::from_min_size(
+                    Default::default(),
+                    [window_size_px.x as f32, window_size_px.y as f32].into(),
+                )),
+                events: vec![egui::Event::PointerMoved(
+                    [
+                        game_state.mouse.screen_pos.x as f32,
+                        game_state.mouse.screen_pos.y as f32,
+                    ]
+                    .into(),
+                )],
+                ..Default::default()
+            });
+            egui_context.set_style(Arc::new(egui_style(&game_state.palette)));
+        }
+
         let mut update_result = crate::game::update(
             &mut game_state,
             &egui_context,
@@ -83,13 +158,42 @@ where
             log::debug!("Finished the frame skip");
         }
 
+        if let (Some(rasterizer), Some(frame_export)) = (&mut rasterizer, &frame_export) {
+            let (_output, paint_batches) = egui_context.end_frame();
+            let egui_meshes = egui_context.tessellate(paint_batches);
+            let mut frame =
+                rasterizer.render(&display, settings.visual_style, &egui_context, &egui_meshes);
+            if let Some(size_px) = frame_export.size_px {
+                frame = rasterizer::fit_to_size(
+                    &frame,
+                    size_px,
+                    game_state.palette.unexplored_background,
+                );
+            }
+            let path = frame_export.dir.join(format!("img{:06}.png", frame_id));
+            frame
+                .save(&path)
+                .map_err(|err| ReplayError::FrameExport(format!("{}: {}", path.display(), err)))?;
+            frame_id += 1;
+        }
+
         match update_result {
             RunningState::Running => {}
             RunningState::NewGame(_new_state) => unreachable!(),
             RunningState::Stopped => break,
             RunningState::Skip => unreachable!(),
         }
+
+        // NOTE: there's nothing to unpause the replay without a
+        // window. This is where `State::seek_replay` stops a paused
+        // replay.
+        if game_state.replay && game_state.paused && game_state.replay_seek.is_none() {
+            break;
+        }
     }
 
-    Ok(())
+    match game_state.replay_error.take() {
+        Some(err) => Err(err),
+        None => Ok(game_state),
+    }
 }
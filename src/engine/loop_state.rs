@@ -71,6 +71,47 @@ pub fn build_texture_from_egui(ctx: &egui::Context) -> (u64, RgbaImage) {
     (egui_texture.version, texture)
 }
 
+pub fn load_glyphmap() -> RgbaImage {
+    let data = &include_bytes!(concat!(env!("OUT_DIR"), "/glyph.png"))[..];
+    let glyphmap = image::load_from_memory_with_format(data, image::ImageFormat::Png)
+        .unwrap_or_else(|e| {
+            log::error!("Error loading the glyph tilemap image: {}", e);
+            log::warn!("Generating an empty 32x32 pixels image as a fallback.");
+            image::DynamicImage::new_rgba8(32, 32)
+        })
+        .to_rgba8();
+    log::debug!("Loaded glyph tilemap.");
+    glyphmap
+}
+
+pub fn load_tilemap() -> RgbaImage {
+    let mut tilemap = {
+        // NOTE: including a manually-edited tileset based on Bountiful Bits
+        let data = &include_bytes!("../../assets/tiles.png")[..];
+        image::load_from_memory_with_format(data, image::ImageFormat::Png)
+            .unwrap_or_else(|e| {
+                log::error!("Error loading the graphics tilemap image: {}", e);
+                log::warn!("Generating an empty 32x32 pixels image as a fallback.");
+                image::DynamicImage::new_rgba8(32, 32)
+            })
+            .to_rgba8()
+    };
+    log::debug!("Loaded the graphics tilemap.");
+    // Normalise the tilemap colours.
+    //
+    // The current tilemap has alpha, but it also sets explicit
+    // colours. This doesn't work with our colour schemes and the
+    // way we do the High effect by overriding some of the
+    // colours. That all expects the original colour to be white
+    // so what we do here is turn every nonzero pixel to white.
+    for pixel in tilemap.pixels_mut() {
+        use image::Pixel;
+        pixel.apply_with_alpha(|channel| if channel == 0 { 0 } else { 255 }, |alpha| alpha);
+    }
+    log::debug!("Normalised the graphics tilemap colours.");
+    tilemap
+}
+
 pub fn egui_set_font_size(ctx: &egui::Context, font_size_px: f32) {
     let font_definitions = {
         use egui::{FontFamily, TextStyle};
@@ -169,43 +210,8 @@ impl LoopState {
             display.display_size.y
         );
 
-        let glyphmap = {
-            let data = &include_bytes!(concat!(env!("OUT_DIR"), "/glyph.png"))[..];
-            image::load_from_memory_with_format(data, image::ImageFormat::Png)
-                .unwrap_or_else(|e| {
-                    log::error!("Error loading the glyph tilemap image: {}", e);
-                    log::warn!("Generating an empty 32x32 pixels image as a fallback.");
-                    image::DynamicImage::new_rgba8(32, 32)
-                })
-                .to_rgba8()
-        };
-        log::debug!("Loaded glyph tilemap.");
-
-        let mut tilemap = {
-            // NOTE: including a manually-edited tileset based on Bountiful Bits
-            let data = &include_bytes!("../../assets/tiles.png")[..];
-            image::load_from_memory_with_format(data, image::ImageFormat::Png)
-                .unwrap_or_else(|e| {
-                    log::error!("Error loading the graphics tilemap image: {}", e);
-                    log::warn!("Generating an empty 32x32 pixels image as a fallback.");
-                    image::DynamicImage::new_rgba8(32, 32)
-                })
-                .to_rgba8()
-        };
-        log::debug!("Loaded the graphics tilemap.");
-        // Normalise the tilemap colours.
-        //
-        // The current tilemap has alpha, but it also sets explicit
-        // colours. This doesn't work with our colour schemes and the
-        // way we do the High effect by overriding some of the
-        // colours. That all expects the original colour to be white
-        // so what we do here is turn every nonzero pixel to white.
-        for pixel in tilemap.pixels_mut() {
-            use image::Pixel;
-            pixel.apply_with_alpha(|channel| if channel == 0 { 0 } else { 255 }, |alpha| alpha);
-        }
-        log::debug!("Normalised the graphics tilemap colours.");
-        let tilemap = tilemap; // Disable `mut`
+        let glyphmap = load_glyphmap();
+        let tilemap = load_tilemap();
 
         // NOTE: we must create at least one frame befoce calling some
         // of the other code later on. Setting the font must also be
@@ -382,7 +388,12 @@ impl LoopState {
             RunningState::NewGame(new_state) => {
                 self.game_state = new_state;
             }
-            RunningState::Stopped => return UpdateResult::QuitRequested,
+            RunningState::Stopped => {
+                if let Some(err) = &self.game_state.replay_error {
+                    log::error!("The replay stopped: {}", err);
+                }
+                return UpdateResult::QuitRequested;
+            }
             RunningState::Skip => unreachable!(),
         }
 
@@ -438,6 +449,12 @@ impl LoopState {
         }
     }
 
+    /// The player switched to another window. They might not come
+    /// back for a while so save the game in case it crashes.
+    pub fn handle_focus_lost(&mut self) {
+        crate::game::autosave(&self.game_state);
+    }
+
     /// The inputs are in LOGICAL pixels.
     pub fn handle_window_size_changed(&mut self, new_width: i32, new_height: i32) {
         log::info!("Window resized to: {} x {}", new_width, new_height);
@@ -0,0 +1,371 @@
+use crate::{
+    color::Color,
+    engine::{
+        self,
+        loop_state::{build_texture_from_egui, load_glyphmap, load_tilemap},
+        Display, Drawcall, Texture, Vertex, VisualStyle,
+    },
+    point::Point,
+};
+
+use egui::ClippedMesh;
+
+use image::{imageops, Rgb, RgbImage, RgbaImage};
+
+/// Renders the game on the CPU into an image. This lets us record
+/// the game in the headless mode where there's no window or OpenGL
+/// context.
+///
+/// It mirrors what the OpenGL backend and its shaders do: every
+/// drawcall and egui mesh becomes a list of triangles that sample
+/// the textures with the nearest-neighbour filter, multiply the
+/// result by the vertex colour and alpha-blend it into the frame.
+pub struct Rasterizer {
+    clear_color: Color,
+    glyphmap: RgbaImage,
+    tilemap: RgbaImage,
+    eguimap: RgbaImage,
+    egui_texture_version: Option<u64>,
+    drawcalls: Vec<Drawcall>,
+    vertices: Vec<Vertex>,
+}
+
+impl Rasterizer {
+    pub fn new(clear_color: Color) -> Self {
+        Self {
+            clear_color,
+            glyphmap: load_glyphmap(),
+            tilemap: load_tilemap(),
+            eguimap: RgbaImage::new(1, 1),
+            egui_texture_version: None,
+            drawcalls: Vec::with_capacity(engine::DRAWCALL_CAPACITY),
+            vertices: Vec::with_capacity(engine::VERTEX_CAPACITY),
+        }
+    }
+
+    /// Render the display and the egui meshes into an image the size
+    /// of the game window.
+    pub fn render(
+        &mut self,
+        display: &Display,
+        visual_style: VisualStyle,
+        egui_context: &egui::Context,
+        egui_meshes: &[ClippedMesh],
+    ) -> RgbImage {
+        if self.egui_texture_version != Some(egui_context.texture().version) {
+            let (egui_texture_version, egui_texture) = build_texture_from_egui(egui_context);
+            self.eguimap = egui_texture;
+            self.egui_texture_version = Some(egui_texture_version);
+        }
+
+        let window_size_px = display.screen_size_px;
+        let clear_color = Rgb([self.clear_color.r, self.clear_color.g, self.clear_color.b]);
+        let mut frame = RgbImage::from_pixel(
+            window_size_px.x as u32,
+            window_size_px.y as u32,
+            clear_color,
+        );
+        let noclip_rect = [0.0, 0.0, window_size_px.x as f32, window_size_px.y as f32];
+
+        self.drawcalls.clear();
+        display.push_drawcalls(visual_style, &mut self.drawcalls);
+        self.vertices.clear();
+        let display_px = display.size_without_padding() * display.tile_size;
+        engine::build_vertices(
+            &self.drawcalls,
+            &mut self.vertices,
+            [display_px.x as f32, display_px.y as f32],
+        );
+        for triangle in self.vertices.chunks_exact(3) {
+            self.draw_triangle(&mut frame, noclip_rect, triangle);
+        }
+
+        for egui::ClippedMesh(rect, mesh) in egui_meshes {
+            let clip_rect = [
+                rect.left_top().x,
+                rect.left_top().y,
+                rect.right_bottom().x,
+                rect.right_bottom().y,
+            ];
+            // NOTE: egui's texture coordinates are normalised, but
+            // the glyph and tile maps use pixel ones. Same as in the
+            // glutin backend.
+            let (texture_id, texture_size) = match mesh.texture_id {
+                egui::TextureId::Egui => (Texture::Egui.into(), [1.0, 1.0]),
+                egui::TextureId::User(id) => {
+                    let texture = self.texture(id as f32);
+                    (id as f32, [texture.width() as f32, texture.height() as f32])
+                }
+            };
+            for indices in mesh.indices.chunks_exact(3) {
+                let mut triangle = [Vertex {
+                    texture_id,
+                    pos_px: [0.0, 0.0],
+                    tile_pos: [0.0, 0.0],
+                    color: [0.0, 0.0, 0.0, 0.0],
+                }; 3];
+                for (vertex, &index) in triangle.iter_mut().zip(indices) {
+                    let egui_vertex = match mesh.vertices.get(index as usize) {
+                        Some(egui_vertex) => egui_vertex,
+                        None => {
+                            log::error!("Can't index into the mesh.vertices");
+                            continue;
+                        }
+                    };
+                    let color = Color {
+                        r: egui_vertex.color.r(),
+                        g: egui_vertex.color.g(),
+                        b: egui_vertex.color.b(),
+                    }
+                    .alpha(egui_vertex.color.a());
+                    vertex.pos_px = [egui_vertex.pos.x, egui_vertex.pos.y];
+                    vertex.tile_pos = [
+                        egui_vertex.uv.x * texture_size[0],
+                        egui_vertex.uv.y * texture_size[1],
+                    ];
+                    vertex.color = color.into();
+                }
+                self.draw_triangle(&mut frame, clip_rect, &triangle);
+            }
+        }
+
+        frame
+    }
+
+    fn texture(&self, texture_id: f32) -> &RgbaImage {
+        if texture_id == f32::from(Texture::Glyph) {
+            &self.glyphmap
+        } else if texture_id == f32::from(Texture::Tilemap) {
+            &self.tilemap
+        } else {
+            &self.eguimap
+        }
+    }
+
+    /// Fill every pixel whose centre lies within the triangle.
+    ///
+    /// The pixels lying exactly on an edge belong to only one of the
+    /// two triangles that share it. Otherwise the diagonal of every
+    /// translucent rectangle would be blended twice.
+    fn draw_triangle(&self, frame: &mut RgbImage, clip_rect: [f32; 4], triangle: &[Vertex]) {
+        let (a, mut b, mut c) = (triangle[0], triangle[1], triangle[2]);
+        let mut area = edge(a.pos_px, b.pos_px, c.pos_px);
+        if area == 0.0 {
+            return;
+        }
+        if area < 0.0 {
+            std::mem::swap(&mut b, &mut c);
+            area = -area;
+        }
+
+        let left = a.pos_px[0]
+            .min(b.pos_px[0])
+            .min(c.pos_px[0])
+            .max(clip_rect[0]);
+        let top = a.pos_px[1]
+            .min(b.pos_px[1])
+            .min(c.pos_px[1])
+            .max(clip_rect[1]);
+        let right = a.pos_px[0]
+            .max(b.pos_px[0])
+            .max(c.pos_px[0])
+            .min(clip_rect[2])
+            .min(frame.width() as f32);
+        let bottom = a.pos_px[1]
+            .max(b.pos_px[1])
+            .max(c.pos_px[1])
+            .min(clip_rect[3])
+            .min(frame.height() as f32);
+        if left >= right || top >= bottom {
+            return;
+        }
+
+        let texture = self.texture(a.texture_id);
+        let solid_fill = a.tile_pos[0] < 0.0 && a.tile_pos[1] < 0.0;
+        let texture_scale = if a.texture_id == f32::from(Texture::Egui) {
+            [texture.width() as f32, texture.height() as f32]
+        } else {
+            [1.0, 1.0]
+        };
+
+        for y in (top.floor() as u32)..(bottom.ceil() as u32) {
+            for x in (left.floor() as u32)..(right.ceil() as u32) {
+                let pixel_centre = [x as f32 + 0.5, y as f32 + 0.5];
+                let weight_a = edge(b.pos_px, c.pos_px, pixel_centre);
+                let weight_b = edge(c.pos_px, a.pos_px, pixel_centre);
+                let weight_c = edge(a.pos_px, b.pos_px, pixel_centre);
+                let inside = covers(weight_a, b.pos_px, c.pos_px)
+                    && covers(weight_b, c.pos_px, a.pos_px)
+                    && covers(weight_c, a.pos_px, b.pos_px);
+                if !inside
+                    || pixel_centre[0] < clip_rect[0]
+                    || pixel_centre[0] >= clip_rect[2]
+                    || pixel_centre[1] < clip_rect[1]
+                    || pixel_centre[1] >= clip_rect[3]
+                {
+                    continue;
+                }
+                let weights = [weight_a / area, weight_b / area, weight_c / area];
+                let interpolate =
+                    |a: f32, b: f32, c: f32| a * weights[0] + b * weights[1] + c * weights[2];
+
+                let mut color = [0.0; 4];
+                for (channel, value) in color.iter_mut().enumerate() {
+                    *value = interpolate(a.color[channel], b.color[channel], c.color[channel]);
+                }
+
+                if !solid_fill {
+                    let u = interpolate(a.tile_pos[0], b.tile_pos[0], c.tile_pos[0]);
+                    let v = interpolate(a.tile_pos[1], b.tile_pos[1], c.tile_pos[1]);
+                    let texel_x =
+                        ((u * texture_scale[0]).floor().max(0.0) as u32).min(texture.width() - 1);
+                    let texel_y =
+                        ((v * texture_scale[1]).floor().max(0.0) as u32).min(texture.height() - 1);
+                    let texel = texture.get_pixel(texel_x, texel_y);
+                    for (value, &texel_channel) in color.iter_mut().zip(&texel.0) {
+                        *value *= f32::from(texel_channel) / 255.0;
+                    }
+                }
+
+                let alpha = color[3].clamp(0.0, 1.0);
+                let pixel = frame.get_pixel_mut(x, y);
+                for (destination, source) in pixel.0.iter_mut().zip(&color) {
+                    let blended = source.clamp(0.0, 1.0) * alpha
+                        + f32::from(*destination) / 255.0 * (1.0 - alpha);
+                    *destination = (blended * 255.0).round() as u8;
+                }
+            }
+        }
+    }
+}
+
+/// Which side of the line going from `a` to `b` the point `p` is on
+/// and how far from it.
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Whether a pixel with the given edge weight is inside the
+/// triangle. Pixels right on the edge go to whichever triangle has
+/// the edge going downwards (or to the left when it's horizontal).
+/// Two triangles sharing the edge always have it going in opposite
+/// directions so exactly one of them gets the pixel.
+fn covers(weight: f32, a: [f32; 2], b: [f32; 2]) -> bool {
+    if weight != 0.0 {
+        return weight > 0.0;
+    }
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    dy > 0.0 || (dy == 0.0 && dx < 0.0)
+}
+
+/// Scale the frame to fit the requested size while keeping its
+/// aspect ratio. The remaining area is filled with `background`.
+pub fn fit_to_size(frame: &RgbImage, size_px: Point, background: Color) -> RgbImage {
+    let (width, height) = (size_px.x.max(1) as u32, size_px.y.max(1) as u32);
+    if frame.dimensions() == (width, height) {
+        return frame.clone();
+    }
+    let scale = (width as f32 / frame.width() as f32).min(height as f32 / frame.height() as f32);
+    let scaled_width = ((frame.width() as f32 * scale).round() as u32).clamp(1, width);
+    let scaled_height = ((frame.height() as f32 * scale).round() as u32).clamp(1, height);
+
+    // NOTE: nearest neighbour keeps the tiles and glyphs crisp when
+    // we're making the frame bigger, but it leaves too many
+    // artefacts when shrinking it.
+    let filter = if scale >= 1.0 {
+        imageops::FilterType::Nearest
+    } else {
+        imageops::FilterType::Triangle
+    };
+    let scaled = imageops::resize(frame, scaled_width, scaled_height, filter);
+
+    let mut result = RgbImage::from_pixel(
+        width,
+        height,
+        Rgb([background.r, background.g, background.b]),
+    );
+    imageops::replace(
+        &mut result,
+        &scaled,
+        (width - scaled_width) / 2,
+        (height - scaled_height) / 2,
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{covers, edge, fit_to_size};
+    use crate::{color::Color, point::Point};
+
+    use image::{Rgb, RgbImage};
+
+    const RED: Rgb<u8> = Rgb([255, 0, 0]);
+    const BLUE: Rgb<u8> = Rgb([0, 0, 255]);
+    const BACKGROUND: Color = Color { r: 0, g: 0, b: 255 };
+
+    #[test]
+    fn fit_same_size() {
+        let frame = RgbImage::from_pixel(4, 3, RED);
+        let result = fit_to_size(&frame, Point::new(4, 3), BACKGROUND);
+        assert_eq!(result, frame);
+    }
+
+    #[test]
+    fn fit_letterboxed() {
+        let frame = RgbImage::from_pixel(4, 2, RED);
+        let result = fit_to_size(&frame, Point::new(8, 8), BACKGROUND);
+        assert_eq!(result.dimensions(), (8, 8));
+        for (_x, y, &pixel) in result.enumerate_pixels() {
+            let expected = if (2..6).contains(&y) { RED } else { BLUE };
+            assert_eq!(pixel, expected, "row {}", y);
+        }
+    }
+
+    #[test]
+    fn fit_pillarboxed() {
+        let frame = RgbImage::from_pixel(2, 4, RED);
+        let result = fit_to_size(&frame, Point::new(8, 4), BACKGROUND);
+        assert_eq!(result.dimensions(), (8, 4));
+        for (x, _y, &pixel) in result.enumerate_pixels() {
+            let expected = if (3..5).contains(&x) { RED } else { BLUE };
+            assert_eq!(pixel, expected, "column {}", x);
+        }
+    }
+
+    #[test]
+    fn fit_smaller() {
+        let frame = RgbImage::from_pixel(100, 50, RED);
+        let result = fit_to_size(&frame, Point::new(10, 10), BACKGROUND);
+        assert_eq!(result.dimensions(), (10, 10));
+        assert_eq!(*result.get_pixel(5, 5), RED);
+        assert_eq!(*result.get_pixel(5, 0), BLUE);
+    }
+
+    #[test]
+    fn edge_sides() {
+        let (a, b) = ([0.0, 0.0], [4.0, 0.0]);
+        assert!(edge(a, b, [1.0, 1.0]) > 0.0);
+        assert!(edge(a, b, [1.0, -1.0]) < 0.0);
+        assert_eq!(edge(a, b, [2.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn covers_inside_and_outside() {
+        let (a, b) = ([0.0, 0.0], [4.0, 0.0]);
+        assert!(covers(1.0, a, b));
+        assert!(!covers(-1.0, a, b));
+    }
+
+    #[test]
+    fn covers_shared_edge_once() {
+        let edges = [
+            ([0.0, 0.0], [4.0, 0.0]),
+            ([0.0, 0.0], [0.0, 4.0]),
+            ([0.0, 0.0], [4.0, 4.0]),
+        ];
+        for (a, b) in edges {
+            assert_ne!(covers(0.0, a, b), covers(0.0, b, a), "{:?} {:?}", a, b);
+        }
+    }
+}
@@ -0,0 +1,667 @@
+use crate::{
+    audio::Audio,
+    color::Color,
+    engine::{
+        loop_state::{egui_set_font_size, egui_style, Metrics},
+        Display, Mouse,
+    },
+    game::RunningState,
+    gamepad::Gamepad,
+    graphic::Graphic,
+    keys::{Key, KeyCode},
+    point::Point,
+    settings::{Settings, Store as SettingsStore},
+    state::State,
+};
+
+use std::{
+    error::Error,
+    fmt::Write as _,
+    io::{self, Write},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use egui::{epaint::ClippedShape, Color32, CtxRef, Pos2, RawInput, Rect, Shape, TextStyle};
+
+/// Size of a map tile in the logical pixels the game lays itself out
+/// in. Every tile takes up two terminal columns so the map isn't
+/// squashed horizontally.
+const TILE_SIZE_PX: i32 = 16;
+
+/// Size of a single terminal character in logical pixels.
+const CELL_WIDTH_PX: f32 = (TILE_SIZE_PX / 2) as f32;
+const CELL_HEIGHT_PX: f32 = TILE_SIZE_PX as f32;
+
+const FPS: i32 = 60;
+
+/// A single character of the terminal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct ScreenCell {
+    glyph: char,
+    foreground: Color,
+    background: Color,
+}
+
+/// The characters and colours of a rendered frame.
+#[derive(Clone, Debug)]
+pub struct Screen {
+    /// Size in terminal columns and rows.
+    size: Point,
+    cells: Vec<ScreenCell>,
+}
+
+impl Screen {
+    pub fn new(size: Point, background: Color) -> Self {
+        let cell = ScreenCell {
+            glyph: ' ',
+            foreground: background,
+            background,
+        };
+        Screen {
+            size,
+            cells: vec![cell; (size.x.max(0) * size.y.max(0)) as usize],
+        }
+    }
+
+    pub fn size(&self) -> Point {
+        self.size
+    }
+
+    fn cell_mut(&mut self, pos: Point) -> Option<&mut ScreenCell> {
+        if pos.x >= 0 && pos.y >= 0 && pos.x < self.size.x && pos.y < self.size.y {
+            self.cells.get_mut((pos.y * self.size.x + pos.x) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// The frame without any colours. Trailing whitespace is removed
+    /// from every line.
+    pub fn to_plain_text(&self) -> String {
+        let mut text = String::with_capacity(self.cells.len() + self.size.y as usize);
+        for row in self.cells.chunks(self.size.x.max(1) as usize) {
+            let line: String = row.iter().map(|cell| cell.glyph).collect();
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Write the ANSI escape codes that draw the frame. Only the
+    /// characters that differ from the `previous` frame are written.
+    fn write_ansi(&self, previous: Option<&Screen>, out: &mut String) {
+        let previous = previous.filter(|previous| previous.size == self.size);
+        let mut cursor = None;
+        let mut colors = None;
+        for (index, cell) in self.cells.iter().enumerate() {
+            if previous.is_some_and(|previous| previous.cells[index] == *cell) {
+                continue;
+            }
+            let pos = Point::new(index as i32 % self.size.x, index as i32 / self.size.x);
+            if cursor != Some(pos) {
+                let _ = write!(out, "\x1b[{};{}H", pos.y + 1, pos.x + 1);
+            }
+            if colors != Some((cell.foreground, cell.background)) {
+                let (fg, bg) = (cell.foreground, cell.background);
+                let _ = write!(
+                    out,
+                    "\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                    fg.r, fg.g, fg.b, bg.r, bg.g, bg.b
+                );
+                colors = Some((fg, bg));
+            }
+            out.push(cell.glyph);
+            cursor = Some(pos + (1, 0));
+        }
+    }
+
+    /// Draw the map tiles. The first column of a tile has the glyph
+    /// and the second one just the background.
+    fn draw_display(&mut self, display: &Display) {
+        for (pos, cell) in display.cells() {
+            let (graphic, color) = if cell.foreground_graphic == Graphic::Empty {
+                (cell.background_graphic, cell.background_color)
+            } else {
+                (cell.foreground_graphic, cell.foreground)
+            };
+            self.draw_tile(pos, graphic, color, Some(cell.empty_color));
+        }
+
+        for tile in &display.offset_tiles {
+            self.draw_tile(tile.pos, tile.graphic, tile.color, None);
+        }
+
+        if display.fade.alpha > 0 {
+            let fade = display.fade;
+            let map_columns = display.size_without_padding().x * 2;
+            for (index, cell) in self.cells.iter_mut().enumerate() {
+                if index as i32 % self.size.x < map_columns {
+                    cell.foreground = blend(cell.foreground, fade.rgb, fade.alpha);
+                    cell.background = blend(cell.background, fade.rgb, fade.alpha);
+                }
+            }
+        }
+    }
+
+    fn draw_tile(&mut self, pos: Point, graphic: Graphic, color: Color, background: Option<Color>) {
+        let left = Point::new(pos.x * 2, pos.y);
+        for (column, glyph) in [(left, char::from(graphic)), (left + (1, 0), ' ')] {
+            if let Some(cell) = self.cell_mut(column) {
+                cell.glyph = glyph;
+                cell.foreground = color;
+                if let Some(background) = background {
+                    cell.background = background;
+                }
+            }
+        }
+    }
+
+    /// Draw the rectangles and text of the UI. Everything else (lines,
+    /// circles, shadows) doesn't translate into characters so it's
+    /// left out.
+    fn draw_egui(&mut self, shapes: &[ClippedShape]) {
+        for ClippedShape(clip_rect, shape) in shapes {
+            self.draw_shape(*clip_rect, shape);
+        }
+    }
+
+    fn draw_shape(&mut self, clip_rect: Rect, shape: &Shape) {
+        match shape {
+            Shape::Vec(shapes) => {
+                for shape in shapes {
+                    self.draw_shape(clip_rect, shape);
+                }
+            }
+            Shape::Rect(rect) if rect.fill.a() > 0 => {
+                let area = rect.rect.intersect(clip_rect);
+                for y in 0..self.size.y {
+                    for x in 0..self.size.x {
+                        let pos = Point::new(x, y);
+                        if area.contains(cell_center_px(pos)) {
+                            if let Some(cell) = self.cell_mut(pos) {
+                                cell.background = blend_egui(cell.background, rect.fill);
+                            }
+                        }
+                    }
+                }
+            }
+            Shape::Text(text) => {
+                let galley = &text.galley;
+                for row in &galley.rows {
+                    for glyph in &row.glyphs {
+                        let center = Pos2::new(
+                            text.pos.x + glyph.pos.x + glyph.size.x / 2.0,
+                            text.pos.y + row.rect.center().y,
+                        );
+                        if !clip_rect.contains(center) {
+                            continue;
+                        }
+                        let color = text.override_text_color.unwrap_or_else(|| {
+                            galley
+                                .job
+                                .sections
+                                .get(glyph.section_index as usize)
+                                .map_or(Color32::WHITE, |section| section.format.color)
+                        });
+                        let pos = Point::new(
+                            (center.x / CELL_WIDTH_PX).floor() as i32,
+                            (center.y / CELL_HEIGHT_PX).floor() as i32,
+                        );
+                        if let Some(cell) = self.cell_mut(pos) {
+                            cell.glyph = glyph.chr;
+                            cell.foreground = blend_egui(cell.background, color);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn cell_center_px(pos: Point) -> Pos2 {
+    Pos2::new(
+        (pos.x as f32 + 0.5) * CELL_WIDTH_PX,
+        (pos.y as f32 + 0.5) * CELL_HEIGHT_PX,
+    )
+}
+
+fn blend(below: Color, above: Color, alpha: u8) -> Color {
+    let mix = |below: u8, above: u8| {
+        ((u32::from(below) * u32::from(255 - alpha) + u32::from(above) * u32::from(alpha)) / 255)
+            as u8
+    };
+    Color {
+        r: mix(below.r, above.r),
+        g: mix(below.g, above.g),
+        b: mix(below.b, above.b),
+    }
+}
+
+/// NOTE: egui colours have their alpha premultiplied.
+fn blend_egui(below: Color, above: Color32) -> Color {
+    let transparency = u32::from(255 - above.a());
+    let mix = |below: u8, above: u8| {
+        (u32::from(above) + u32::from(below) * transparency / 255).min(255) as u8
+    };
+    Color {
+        r: mix(below.r, above.r()),
+        g: mix(below.g, above.g()),
+        b: mix(below.b, above.b()),
+    }
+}
+
+/// Runs the game frames and renders them into a `Screen`.
+///
+/// This is everything the terminal backend does short of talking to
+/// the terminal, so the tests can use it to check what's on screen.
+pub struct Renderer<S> {
+    settings_store: S,
+    settings: Settings,
+    egui_context: CtxRef,
+    display: Display,
+    audio: Audio,
+    gamepad: Gamepad,
+    screen: Screen,
+}
+
+impl<S> Renderer<S>
+where
+    S: SettingsStore,
+{
+    /// `size` is in terminal columns and rows.
+    pub fn new(settings_store: S, size: Point) -> Self {
+        let mut settings = settings_store.load();
+        settings.tile_size = TILE_SIZE_PX;
+        settings.text_size = TILE_SIZE_PX;
+
+        // NOTE: pick the font size that makes a single character as
+        // wide as a terminal column. egui only picks up new fonts in
+        // the next frame so we need to run an empty one first.
+        let mut egui_context = CtxRef::default();
+        egui_set_font_size(&egui_context, TILE_SIZE_PX as f32);
+        egui_context.begin_frame(RawInput::default());
+        let glyph_width_px = egui_context.fonts().glyph_width(TextStyle::Body, 'M');
+        let _ = egui_context.end_frame();
+        egui_set_font_size(
+            &egui_context,
+            TILE_SIZE_PX as f32 * CELL_WIDTH_PX / glyph_width_px,
+        );
+
+        let mut renderer = Renderer {
+            settings_store,
+            settings,
+            egui_context,
+            display: Display::new(Point::from_i32(TILE_SIZE_PX), TILE_SIZE_PX, TILE_SIZE_PX),
+            audio: Audio::new(None),
+            gamepad: Gamepad::new(),
+            screen: Screen::new(Point::zero(), Color::default()),
+        };
+        renderer.resize(size);
+        renderer
+    }
+
+    /// `size` is in terminal columns and rows.
+    pub fn resize(&mut self, size: Point) {
+        let size = Point::new(size.x.max(2), size.y.max(1));
+        let screen_size_px = Point::new(
+            size.x * CELL_WIDTH_PX as i32,
+            size.y * CELL_HEIGHT_PX as i32,
+        );
+        log::info!("Terminal size: {} x {}", size.x, size.y);
+        self.settings.window_width = screen_size_px.x as u32;
+        self.settings.window_height = screen_size_px.y as u32;
+        self.display = Display::new(screen_size_px, TILE_SIZE_PX, TILE_SIZE_PX);
+        self.screen = Screen::new(size, Color::default());
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    /// Run a single frame of the game and render it.
+    pub fn update(&mut self, state: &mut State, dt: Duration, keys: &[Key]) -> RunningState {
+        // NOTE: the game lays itself out in pixels based on these and
+        // they must match the terminal cells. Don't let the settings
+        // window change them.
+        self.settings.tile_size = TILE_SIZE_PX;
+        self.settings.text_size = TILE_SIZE_PX;
+        let metrics = &Metrics {
+            tile_width_px: TILE_SIZE_PX,
+            text_width_px: TILE_SIZE_PX,
+        };
+
+        self.egui_context.begin_frame(RawInput {
+            screen_rect: Some(Rect::from_min_size(
+                Default::default(),
+                [
+                    self.settings.window_width as f32,
+                    self.settings.window_height as f32,
+                ]
+                .into(),
+            )),
+            ..Default::default()
+        });
+        self.egui_context
+            .set_style(Arc::new(egui_style(&state.palette)));
+
+        let mut update_result = crate::game::update(
+            state,
+            &self.egui_context,
+            dt,
+            FPS,
+            keys,
+            Mouse::new(),
+            &mut self.gamepad,
+            &mut self.settings,
+            metrics,
+            &mut self.settings_store,
+            &mut self.display,
+            &mut self.audio,
+        );
+        while std::matches!(update_result, RunningState::Skip) {
+            update_result = crate::game::update(
+                state,
+                &self.egui_context,
+                dt,
+                FPS,
+                &[],
+                Mouse::new(),
+                &mut self.gamepad,
+                &mut self.settings,
+                metrics,
+                &mut self.settings_store,
+                &mut self.display,
+                &mut self.audio,
+            );
+        }
+
+        let (_output, shapes) = self.egui_context.end_frame();
+        self.screen = Screen::new(self.screen.size, state.palette.unexplored_background);
+        self.screen.draw_display(&self.display);
+        self.screen.draw_egui(&shapes);
+
+        update_result
+    }
+}
+
+/// Puts the terminal into the raw mode and the alternate screen and
+/// restores it when dropped -- even when the game panics.
+struct RawTerminal {
+    original: libc::termios,
+}
+
+impl RawTerminal {
+    fn enable() -> io::Result<Self> {
+        // SAFETY: `termios` is a plain C struct that `tcgetattr` fills in.
+        #[allow(unsafe_code)]
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: `original` is a valid `termios` we own for the
+        // duration of the call.
+        #[allow(unsafe_code)]
+        let result = unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = original;
+        // SAFETY: `raw` is a valid `termios` initialised by
+        // `tcgetattr`. `cfmakeraw` only changes its flags.
+        #[allow(unsafe_code)]
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+        }
+        // NOTE: don't block on `read`. We check the keys every frame.
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: `raw` is a valid `termios` and the call only reads
+        // it.
+        #[allow(unsafe_code)]
+        let result = unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut stdout = io::stdout();
+        // Alternate screen, hide the cursor, clear, report the focus.
+        stdout.write_all(b"\x1b[?1049h\x1b[?25l\x1b[2J\x1b[?1004h")?;
+        stdout.flush()?;
+        Ok(RawTerminal { original })
+    }
+
+    /// The terminal size in columns and rows.
+    fn size() -> Option<Point> {
+        // SAFETY: `winsize` is a plain C struct that `ioctl` fills in.
+        #[allow(unsafe_code)]
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        // SAFETY: `TIOCGWINSZ` writes a `winsize` into the pointer and
+        // `size` is one we own for the duration of the call.
+        #[allow(unsafe_code)]
+        let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+        if result == 0 && size.ws_col > 0 && size.ws_row > 0 {
+            Some(Point::new(i32::from(size.ws_col), i32::from(size.ws_row)))
+        } else {
+            None
+        }
+    }
+
+    /// Whatever's been typed since the last call.
+    fn read(&self) -> Vec<u8> {
+        let mut buffer = [0_u8; 256];
+        // SAFETY: the pointer and length describe `buffer` which
+        // outlives the call. `read` writes at most `buffer.len()`
+        // bytes into it.
+        #[allow(unsafe_code)]
+        let read = unsafe {
+            libc::read(
+                libc::STDIN_FILENO,
+                buffer.as_mut_ptr().cast::<libc::c_void>(),
+                buffer.len(),
+            )
+        };
+        // NOTE: `read` returns -1 on error.
+        buffer[..read.max(0) as usize].to_vec()
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        // Stop reporting the focus, reset the colours, show the
+        // cursor, leave the alternate screen.
+        let _ = stdout.write_all(b"\x1b[?1004l\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        // SAFETY: `self.original` is the valid `termios` that
+        // `tcgetattr` returned in `enable`. The call only reads it.
+        // There's nothing to do if restoring it fails.
+        #[allow(unsafe_code)]
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// What the terminal sends when it loses focus.
+const FOCUS_OUT: &[u8] = b"\x1b[O";
+
+/// Turn what the terminal sends into keys.
+///
+/// The arrows come as escape sequences with the modifiers encoded in
+/// them. Home, End, Page Up and Page Down are what the numpad sends
+/// with Num Lock off so they're the diagonal numpad keys.
+pub fn parse_keys(mut bytes: &[u8]) -> Vec<Key> {
+    let mut keys = vec![];
+    while let Some((&byte, rest)) = bytes.split_first() {
+        bytes = rest;
+        let key = match byte {
+            0x1b => escape_sequence(&mut bytes),
+            b'\r' | b'\n' => Some(KeyCode::Enter.into()),
+            b' ' => Some(KeyCode::Space.into()),
+            b'?' => Some(KeyCode::QuestionMark.into()),
+            0x7f => Some(KeyCode::Backspace.into()),
+            b'a'..=b'z' => letter(byte - b'a'),
+            b'A'..=b'Z' => letter(byte - b'A').map(|key| Key { shift: true, ..key }),
+            b'0'..=b'9' => digit(byte - b'0'),
+            // Ctrl + letter
+            1..=26 => letter(byte - 1).map(|key| Key { ctrl: true, ..key }),
+            _ => None,
+        };
+        keys.extend(key);
+    }
+    keys
+}
+
+fn escape_sequence(bytes: &mut &[u8]) -> Option<Key> {
+    let introducer = bytes.first().copied();
+    if introducer != Some(b'[') && introducer != Some(b'O') {
+        return Some(KeyCode::Esc.into());
+    }
+
+    // The parameters are numbers separated by `;` and the sequence
+    // ends with a letter or `~`.
+    let end = bytes
+        .iter()
+        .skip(1)
+        .position(|byte| !byte.is_ascii_digit() && *byte != b';')
+        .map(|position| position + 1)?;
+    let parameters: Vec<u32> = std::str::from_utf8(&bytes[1..end])
+        .unwrap_or_default()
+        .split(';')
+        .map(|parameter| parameter.parse().unwrap_or(1))
+        .collect();
+    let last = bytes[end];
+    *bytes = &bytes[end + 1..];
+
+    use self::KeyCode::*;
+    let code = match (last, parameters[0]) {
+        (b'A', _) => Up,
+        (b'B', _) => Down,
+        (b'C', _) => Right,
+        (b'D', _) => Left,
+        (b'H', _) | (b'~', 1 | 7) => NumPad7,
+        (b'F', _) | (b'~', 4 | 8) => NumPad1,
+        (b'~', 5) => NumPad9,
+        (b'~', 6) => NumPad3,
+        (b'E', _) => NumPad5,
+        (b'P', _) | (b'~', 11) => F1,
+        (b'Q', _) | (b'~', 12) => F2,
+        (b'R', _) | (b'~', 13) => F3,
+        (b'S', _) | (b'~', 14) => F4,
+        (b'~', 15) => F5,
+        (b'~', 17) => F6,
+        (b'~', 18) => F7,
+        (b'~', 19) => F8,
+        (b'~', 20) => F9,
+        (b'~', 21) => F10,
+        (b'~', 23) => F11,
+        (b'~', 24) => F12,
+        _ => return None,
+    };
+    // NOTE: the modifier parameter is 1 + a bit mask of the modifiers.
+    let modifiers = parameters
+        .get(1)
+        .map_or(0, |modifier| modifier.saturating_sub(1));
+    Some(Key {
+        code,
+        shift: modifiers & 1 != 0,
+        alt: modifiers & 2 != 0,
+        ctrl: modifiers & 4 != 0,
+        logo: false,
+    })
+}
+
+fn letter(index: u8) -> Option<Key> {
+    use self::KeyCode::*;
+    let letters = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    letters.get(usize::from(index)).map(|&code| code.into())
+}
+
+fn digit(index: u8) -> Option<Key> {
+    use self::KeyCode::*;
+    let digits = [D0, D1, D2, D3, D4, D5, D6, D7, D8, D9];
+    digits.get(usize::from(index)).map(|&code| code.into())
+}
+
+/// Play the game in the terminal the program is running in.
+///
+/// `Ctrl+C` quits the game.
+pub fn main_loop<S>(
+    window_title: &str,
+    settings_store: S,
+    initial_state: Box<State>,
+) -> Result<(), Box<dyn Error>>
+where
+    S: SettingsStore,
+{
+    let terminal = RawTerminal::enable()?;
+    let mut stdout = io::stdout();
+    let _ = write!(stdout, "\x1b]0;{}\x07", window_title);
+
+    let mut terminal_size = RawTerminal::size().unwrap_or(Point::new(80, 24));
+    let mut renderer = Renderer::new(settings_store, terminal_size);
+    let mut previous_screen: Option<Screen> = None;
+    let mut game_state = initial_state;
+    let frame_duration = Duration::from_secs(1) / FPS as u32;
+    let mut previous_frame = Instant::now();
+    let mut output = String::new();
+
+    loop {
+        let frame_start = Instant::now();
+        let dt = frame_start - previous_frame;
+        previous_frame = frame_start;
+
+        if let Some(size) = RawTerminal::size() {
+            if size != terminal_size {
+                terminal_size = size;
+                renderer.resize(size);
+                previous_screen = None;
+                output.push_str("\x1b[2J");
+            }
+        }
+
+        let input = terminal.read();
+        if input
+            .windows(FOCUS_OUT.len())
+            .any(|bytes| bytes == FOCUS_OUT)
+        {
+            crate::game::autosave(&game_state);
+        }
+        let keys = parse_keys(&input);
+        let quit = KeyCode::C.into();
+        if keys.contains(&Key { ctrl: true, ..quit }) {
+            break;
+        }
+
+        let update_result = renderer.update(&mut game_state, dt, &keys);
+
+        renderer
+            .screen()
+            .write_ansi(previous_screen.as_ref(), &mut output);
+        stdout.write_all(output.as_bytes())?;
+        stdout.flush()?;
+        output.clear();
+        previous_screen = Some(renderer.screen().clone());
+
+        match update_result {
+            RunningState::Running => {}
+            RunningState::NewGame(new_state) => {
+                game_state = new_state;
+            }
+            RunningState::Stopped => {
+                if let Some(err) = &game_state.replay_error {
+                    log::error!("The replay stopped: {}", err);
+                }
+                break;
+            }
+            RunningState::Skip => unreachable!(),
+        }
+
+        if let Some(remaining) = frame_duration.checked_sub(frame_start.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+
+    Ok(())
+}
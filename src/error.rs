@@ -1,7 +1,11 @@
 #![allow(dead_code, unused_macros)]
 
+use crate::state::{Verification, VerificationDiff};
+
 use std::fmt;
 
+use serde::Serialize;
+
 #[derive(Debug)]
 pub struct Error {
     pub message: String,
@@ -22,3 +26,82 @@ impl fmt::Display for Error {
 }
 
 impl std::error::Error for Error {}
+
+/// Why a replay couldn't be played back.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The replay file couldn't be read or the exported frames
+    /// couldn't be written.
+    Io(std::io::Error),
+
+    /// The line (counting from 1) is not a valid part of the replay.
+    Parse { line: usize, message: String },
+
+    /// The replay was recorded in a newer format than this build
+    /// understands.
+    UnsupportedVersion { version: u64, supported: u32 },
+
+    /// The game state doesn't match what the replay recorded.
+    Desync(Box<Desync>),
+
+    /// The game is still going but there are no more inputs.
+    MissingInput { tick_id: i32 },
+
+    /// The player died while there are still inputs left in the
+    /// replay.
+    UnusedInputs { tick_id: i32, remaining: usize },
+
+    /// Rendering a frame into an image failed.
+    FrameExport(String),
+}
+
+/// The first tick where a replay doesn't match what this build is
+/// doing.
+#[derive(Debug, Serialize)]
+pub enum Desync {
+    /// The replay recorded the full verification (i.e. it was made
+    /// with `--debug`) so we know exactly what's different.
+    Verification {
+        tick_id: i32,
+        diff: VerificationDiff,
+    },
+
+    /// The replay only recorded a hash. All we can do is show the
+    /// state this build arrived at instead.
+    Hash {
+        tick_id: i32,
+        expected_hash: String,
+        actual: Verification,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::ReplayError::*;
+        match self {
+            Io(err) => write!(f, "{}", err),
+            Parse { line, message } => write!(f, "Invalid replay on line {}: {}", line, message),
+            UnsupportedVersion { version, supported } => write!(
+                f,
+                "Unsupported replay format version: {}. The highest supported version is: {}.",
+                version, supported
+            ),
+            Desync(desync) => write!(f, "The replay desynced: {:?}", desync),
+            MissingInput { tick_id } => write!(f, "Could not load input for tick {}", tick_id),
+            UnusedInputs { tick_id, remaining } => write!(
+                f,
+                "Game quit too early at tick {} -- there are still {} commands queued up.",
+                tick_id, remaining
+            ),
+            FrameExport(message) => write!(f, "Could not export the frame: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(err: std::io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
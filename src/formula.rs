@@ -52,6 +52,27 @@ pub const VICTORY_NPC_DISTANCE: InclusiveRange = InclusiveRange(80, 120);
 
 pub const CHUNK_BASELINE_DENSITY: f32 = 0.39;
 pub const CHUNK_DENSITY_VARIABILITY: (f32, f32) = (-0.30, 0.15);
+pub const CHUNK_DENSITY_LIMITS: (f32, f32) = (0.05, 0.7);
+
+/// The chunks closer to the start than this (in tiles) are as easy
+/// as they get. Further out, they get harder until
+/// `REGION_HARDEST_DISTANCE`.
+pub const REGION_EASY_DISTANCE: f32 = 32.0;
+pub const REGION_HARDEST_DISTANCE: f32 = 256.0;
+/// Monsters per 1000 empty tiles on top of the usual ones in the
+/// hardest regions.
+pub const REGION_EXTRA_MONSTER_CHANCE: f32 = 30.0;
+/// Added to the `state_of_mind` of every dose in the hardest regions.
+pub const REGION_DOSE_PURITY_BONUS: f32 = 15.0;
+/// The portion of food that's missing in the hardest regions.
+pub const REGION_FOOD_PENALTY: f32 = 0.4;
+
+/// The distance (in tiles) between the points of the biome noise.
+pub const BIOME_SIZE: i32 = 256;
+/// How much the biome adds to (or removes from) the tree density.
+pub const BIOME_DENSITY_VARIABILITY: f32 = 0.12;
+/// The portion of food the biome adds (or removes).
+pub const BIOME_FOOD_VARIABILITY: f32 = 0.6;
 
 #[cfg(feature = "recording")]
 pub const DOSE_PREFAB: Item = Item {
@@ -139,6 +160,9 @@ pub const PATHFINDING_DOSE_RESIST_LIMIT: i32 = 50;
 pub const PATHFINDING_VNPC_REACHABILITY_LIMIT: i32 = 500;
 
 pub const PLAYER_PATH_WALKING_DELAY: Duration = Duration::from_millis(300);
+/// How long the autoplay bot waits between its moves so the player
+/// can follow what's going on.
+pub const AUTOPLAY_DELAY: Duration = Duration::from_millis(150);
 
 pub const ANIMATION_ATTACK_DURATION: Duration = Duration::from_millis(75);
 pub const ANIMATION_MOVE_DURATION: Duration = Duration::from_millis(100);
@@ -0,0 +1,248 @@
+use crate::{
+    engine::{headless, Mouse},
+    generators::Landscape,
+    keys::{Key, KeyCode},
+    palette::Palette,
+    random::Random,
+    settings::NoOpStore,
+    state::{
+        self, Challenge, DisplaySettings, Input, ReplayHeader, ReplayStream, State,
+        VerificationWrapper, REPLAY_FORMAT_VERSION,
+    },
+    util,
+};
+
+use std::{
+    any::Any,
+    error::Error,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+/// Where the replays of the games that crashed go.
+pub const CRASH_DIR: &str = "fuzz-crashes";
+
+/// How many ticks a fuzzed game lasts at most. Most of them end much
+/// sooner because the player dies.
+const MAX_TICKS: i32 = 3000;
+
+/// NOTE: these keys only do something in the game window. We stay
+/// away from the ones that open the menu, help, etc. because the
+/// replays can't start a new game and that's not what we're testing
+/// anyway.
+const GAME_KEYS: [KeyCode; 27] = [
+    KeyCode::NumPad1,
+    KeyCode::NumPad2,
+    KeyCode::NumPad3,
+    KeyCode::NumPad4,
+    KeyCode::NumPad6,
+    KeyCode::NumPad7,
+    KeyCode::NumPad8,
+    KeyCode::NumPad9,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Y,
+    KeyCode::K,
+    KeyCode::U,
+    KeyCode::H,
+    KeyCode::L,
+    KeyCode::B,
+    KeyCode::J,
+    KeyCode::N,
+    KeyCode::E,
+    KeyCode::Enter,
+    KeyCode::D1,
+    KeyCode::D2,
+    KeyCode::D3,
+    KeyCode::D4,
+    KeyCode::D5,
+];
+
+const DIRECTION_KEYS: [KeyCode; 8] = [
+    KeyCode::NumPad1,
+    KeyCode::NumPad2,
+    KeyCode::NumPad3,
+    KeyCode::NumPad4,
+    KeyCode::NumPad6,
+    KeyCode::NumPad7,
+    KeyCode::NumPad8,
+    KeyCode::NumPad9,
+];
+
+const ITEM_KEYS: [KeyCode; 5] = [
+    KeyCode::D1,
+    KeyCode::D2,
+    KeyCode::D3,
+    KeyCode::D4,
+    KeyCode::D5,
+];
+
+/// How the fuzzer presses the keys.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum KeyChoice {
+    /// Any of the game keys with any modifiers.
+    Random,
+    /// Mostly keep walking in the same direction and use an item
+    /// every now and then. This gets the player further out than
+    /// mashing random keys.
+    Biased,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub games: u32,
+    /// The replays of the games that crashed.
+    pub crashes: Vec<PathBuf>,
+}
+
+/// Play `iterations` games with random seeds, challenges and keys.
+///
+/// Every game is written out as a regular replay and played back
+/// without a window. If it panics, the replay is saved into
+/// `CRASH_DIR` so the crash can be reproduced by playing it back.
+pub fn run(iterations: u32) -> Result<Summary, Box<dyn Error>> {
+    let mut rng = Random::new();
+    let candidate_path =
+        std::env::temp_dir().join(format!("dose-response-fuzz-{}", std::process::id()));
+    let mut crashes = vec![];
+
+    for iteration in 1..=iterations {
+        let header = random_header(&mut rng);
+        let keys = if rng.rand_float() < 0.5 {
+            KeyChoice::Random
+        } else {
+            KeyChoice::Biased
+        };
+        let inputs = random_inputs(&mut rng, keys);
+        log::info!(
+            "Fuzzing game {} of {}: seed: {}, {:?}, {} ticks of {:?} keys",
+            iteration,
+            iterations,
+            header.seed,
+            header.challenge,
+            inputs.len(),
+            keys
+        );
+        state::write_replay(&candidate_path, &header, &inputs)?;
+        let state = State::load_replay_for_tools(&candidate_path)?;
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            headless::run(NoOpStore, Box::new(state), None)
+        }));
+        match result {
+            Ok(Ok(final_state)) => {
+                log::info!("Game {} finished at turn {}.", iteration, final_state.turn)
+            }
+            // NOTE: the inputs are random so they'll usually run out
+            // before the game is over or the other way round. That's
+            // fine, we're only looking for crashes.
+            Ok(Err(err)) => log::info!("Game {} stopped: {}", iteration, err),
+            Err(panic) => {
+                fs::create_dir_all(CRASH_DIR)?;
+                let crash_path =
+                    Path::new(CRASH_DIR).join(format!("crash-{}-{}", header.seed, iteration));
+                fs::copy(&candidate_path, &crash_path)?;
+                log::error!(
+                    "Game {} crashed: {}. Saved its replay to: {}",
+                    iteration,
+                    panic_message(panic.as_ref()),
+                    crash_path.display()
+                );
+                crashes.push(crash_path);
+            }
+        }
+    }
+
+    let _ = fs::remove_file(&candidate_path);
+    Ok(Summary {
+        games: iterations,
+        crashes,
+    })
+}
+
+fn random_header(rng: &mut Random) -> ReplayHeader {
+    let mut coin_flip = || rng.rand_float() < 0.5;
+    let challenge = Challenge {
+        hide_unseen_tiles: coin_flip(),
+        fast_depression: coin_flip(),
+        one_chance: coin_flip(),
+    };
+    let landscape = if coin_flip() {
+        Landscape::Urban
+    } else {
+        Landscape::Forest
+    };
+    ReplayHeader {
+        replay_format_version: REPLAY_FORMAT_VERSION,
+        version: crate::metadata::VERSION.into(),
+        commit: crate::metadata::GIT_HASH.into(),
+        seed: util::random_seed(),
+        world_size: crate::WORLD_SIZE,
+        map_size: crate::point::Point::from_i32(crate::DISPLAYED_MAP_SIZE),
+        panel_width: crate::PANEL_WIDTH,
+        challenge,
+        palette: Palette::classic(),
+        display: DisplaySettings::default(),
+        invincible: false,
+        stream: ReplayStream::Inputs,
+        landscape,
+        regions: true,
+    }
+}
+
+fn random_inputs(rng: &mut Random, keys: KeyChoice) -> Vec<Input> {
+    let tick_count = rng.range_inclusive(100, MAX_TICKS);
+    let mut direction = KeyCode::NumPad8;
+    let mut inputs = vec![];
+    for tick_id in 1..=tick_count {
+        let mut pressed = vec![];
+        // NOTE: the player can't act every tick anyway.
+        if rng.rand_float() < 0.5 {
+            let key = match keys {
+                KeyChoice::Random => {
+                    let code = *rng.choose_with_fallback(&GAME_KEYS, &KeyCode::NumPad8);
+                    let mut modifier = || rng.rand_float() < 0.1;
+                    Key {
+                        code,
+                        alt: modifier(),
+                        ctrl: modifier(),
+                        shift: modifier(),
+                        logo: modifier(),
+                    }
+                }
+                KeyChoice::Biased => {
+                    let roll = rng.rand_float();
+                    if roll < 0.05 {
+                        (*rng.choose_with_fallback(&ITEM_KEYS, &KeyCode::D1)).into()
+                    } else {
+                        if roll > 0.8 {
+                            direction = *rng.choose_with_fallback(&DIRECTION_KEYS, &direction);
+                        }
+                        direction.into()
+                    }
+                }
+            };
+            pressed.push(key);
+        }
+        inputs.push(Input {
+            keys: pressed,
+            mouse: Mouse::new(),
+            tick_id,
+            verification: VerificationWrapper::None,
+        });
+    }
+    inputs
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
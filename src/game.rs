@@ -2,9 +2,11 @@ use crate::{
     ai,
     animation::{self, AreaOfEffect},
     audio::{Audio, Effect},
+    autoplay,
     blocker::Blocker,
     color,
     engine::{Display, Mouse, TextMetrics},
+    error::{Desync, ReplayError},
     formula,
     gamepad::Gamepad,
     graphic::Graphic,
@@ -14,32 +16,38 @@ use crate::{
     monster::{self, CompanionBonus},
     palette::Palette,
     pathfinding,
-    player::{self, Modifier},
+    player::{self, CauseOfDeath, Modifier},
     point::{self, Point},
     random::Random,
     ranged_int::{InclusiveRange, Ranged},
     rect::Rectangle,
-    render,
+    render, save,
     settings::{Settings, Store as SettingsStore},
+    share_code::ShareCode,
     state::{
-        self, Challenge, Command, GameSession, Input, MotionAnimation, Side, State,
-        VerificationWrapper,
+        self, Command, GameSession, Input, MotionAnimation, ReplayCommand, ReplayStream, Side,
+        State, VerificationWrapper,
     },
     stats::{FrameStats, Stats},
     timer::{Stopwatch, Timer},
     ui, util,
     window::{self, Window},
-    windows::{endgame, help, main_menu, message, settings, sidebar},
+    windows::{endgame, help, load_game, main_menu, message, new_game, settings, sidebar},
     world::World,
 };
 
-use std::{
-    collections::{HashMap, VecDeque},
-    time::Duration,
-};
+use std::{collections::VecDeque, time::Duration};
 
 use egui::{CtxRef, Ui};
 
+/// How many ticks Shift + Left / Right moves the replay by. That's
+/// about 10 seconds of the real-time playback.
+const REPLAY_SEEK_JUMP_TICKS: i32 = 600;
+
+/// While seeking, render a frame once per this many re-simulated
+/// ticks.
+const REPLAY_SEEK_FRAME_TICKS: i32 = 100;
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Action {
     Move(Point),
@@ -83,14 +91,47 @@ pub fn update(
     if state.replay {
         let mut keys = Keys::new();
         keys.extend(new_keys.iter().copied());
-        state.paused = if keys.matches_code(KeyCode::Space) {
-            !state.paused
-        } else {
-            state.paused
-        };
+        if keys.matches_code(KeyCode::Space) {
+            // NOTE: while seeking, only change what happens once we
+            // reach the target tick.
+            match state.replay_seek {
+                Some(ref mut seek) => seek.paused = !seek.paused,
+                None => state.paused = !state.paused,
+            }
+        }
+
+        if keys.matches_code(KeyCode::Up) {
+            state.replay_speed = state.replay_speed.faster();
+            log::info!("Replay speed: {}", state.replay_speed);
+        }
+        if keys.matches_code(KeyCode::Down) {
+            state.replay_speed = state.replay_speed.slower();
+            log::info!("Replay speed: {}", state.replay_speed);
+        }
+
+        process_replay_seek_keys(state, &mut keys);
+
+        if let Some(seek) = state.replay_seek {
+            if state.tick_id >= seek.target_tick || state.window_stack.top() != Window::Game {
+                log::info!("Replay seek finished at tick {}", state.tick_id);
+                state.replay_seek = None;
+                state.paused = seek.paused;
+            }
+        }
+
+        advance_replay_tick_frames(state);
+
+        if state.window_stack.top() == Window::Game
+            && !state.replay_paused()
+            && state.player.alive()
+        {
+            state.take_replay_snapshot_if_due();
+        }
     }
 
-    if state.window_stack.top() == Window::Game && !state.paused {
+    let dt = frame_dt(state, dt);
+
+    if state.window_stack.top() == Window::Game && !state.replay_paused() {
         state.previous_tick = state.tick_id;
         state.tick_id += 1;
         log::debug!("Starting new tick with ID: {}", state.tick_id);
@@ -227,46 +268,86 @@ pub fn update(
         );
     }
 
-    if state.replay && state.player.alive() && !state.paused {
+    if state.replay && state.replay_stream() == ReplayStream::Commands && !state.replay_paused() {
+        // NOTE: the recorded commands replace the keys and the mouse.
+        // We queue up the next one once the previous one's been
+        // carried out and the explosion (if any) is over.
+        state.mouse = Mouse::new();
+        let remaining_commands = state
+            .recorded_commands
+            .len()
+            .saturating_sub(state.commands_executed as usize);
+        let next_command = state
+            .recorded_commands
+            .get(state.commands_executed as usize)
+            .map(|recorded| recorded.command.clone());
+        if !state.player.alive() {
+            if remaining_commands > 0 {
+                log::error!(
+                    "Game quit too early -- there are still {} commands left.",
+                    remaining_commands
+                );
+                state.replay_error = Some(ReplayError::UnusedInputs {
+                    tick_id: state.tick_id,
+                    remaining: remaining_commands,
+                });
+                return RunningState::Stopped;
+            }
+        } else if let Some(command) = next_command {
+            if state.commands.is_empty() && state.explosion_animation.is_none() {
+                state.commands.push_back(command);
+            }
+        }
+    } else if state.replay && state.player.alive() && !state.replay_paused() {
         let replay_input_index = state.tick_id as usize - 1;
         assert_eq!(state.tick_id, state.previous_tick + 1);
         if let Some(input) = state.inputs.get(replay_input_index) {
             assert_eq!(state.tick_id, input.tick_id);
 
-            match &input.verification {
-                VerificationWrapper::Verification(expected) => {
-                    let actual = state.verification();
-                    verify_states(expected, &actual);
-                }
-                VerificationWrapper::Hash(replay_hash_bytes) => {
-                    let replay_hash = blake3::Hash::from_bytes(*replay_hash_bytes);
-                    let actual_hash = state.verification().hash();
-                    assert_eq!(replay_hash, actual_hash);
+            if state.replay_ticks.is_some() {
+                let tick = state::ReplayTick {
+                    actual: state.verification(),
+                    recorded: input.verification.clone(),
+                };
+                if let Some(replay_ticks) = &mut state.replay_ticks {
+                    replay_ticks.push(tick);
                 }
-
-                // NOTE: by definition, the lack of a verification is
-                // treated as an automatic pass:
-                VerificationWrapper::None => (),
+            } else if let Some(desync) = input.verification.desync(&state.verification()) {
+                log_desync(&desync);
+                state.replay_error = Some(ReplayError::Desync(Box::new(desync)));
+                return RunningState::Stopped;
             }
 
             state.keys.extend(input.keys.iter().copied());
             state.mouse = input.mouse;
+        } else if state.replay_ticks.is_some() {
+            log::warn!(
+                "The replay ran out of inputs at tick {}, stopping.",
+                state.tick_id
+            );
+            return RunningState::Stopped;
         } else {
-            #[allow(clippy::panic)]
-            {
-                panic!("Could not load input for {}", state.tick_id);
-            }
+            log::error!("Could not load input for {}", state.tick_id);
+            state.replay_error = Some(ReplayError::MissingInput {
+                tick_id: state.tick_id,
+            });
+            return RunningState::Stopped;
         }
 
-        #[allow(clippy::panic)]
         if !state.player.alive() && !state.inputs.is_empty() {
-            panic!(
-                "Game quit too early -- there are still {} \
-                         commands queued up.",
+            log::error!(
+                "Game quit too early -- there are still {} commands queued up.",
                 state.inputs.len()
             );
+            state.replay_error = Some(ReplayError::UnusedInputs {
+                tick_id: state.tick_id,
+                remaining: state.inputs.len(),
+            });
+            return RunningState::Stopped;
         }
-    } else {
+    } else if state.replay_tick_frames == 0 {
+        // NOTE: a slowed down replay keeps the input of the tick
+        // it's holding instead.
         state.keys.extend(new_keys.iter().copied());
         state.mouse = mouse;
     }
@@ -275,7 +356,7 @@ pub fn update(
     if ((state.side == Side::Victory || !state.player.alive()) && state.exit_after)
         || (state.replay
             && state.exit_after
-            && (state.inputs.is_empty()
+            && ((state.inputs.is_empty() && state.recorded_commands.is_empty())
                 || (!state.player.alive() && state.screen_fading.is_none())))
     {
         show_exit_stats(&state.stats);
@@ -295,7 +376,7 @@ pub fn update(
         && state.player.alive()
         && state.side == Side::Player
         && state.window_stack.top() == Window::Game
-        && !state.paused
+        && !state.replay_paused()
         && state.player.ap() > 0
         && state.explosion_animation.is_none()
         && state.extra_animations.is_empty()
@@ -398,6 +479,20 @@ pub fn update(
                     // Clear any fade set by the gameplay rendering
                     display.fade = color::INVISIBLE;
                 }
+                Window::LoadGame => {
+                    if top_level {
+                        game_update_result = load_game::process(state, ui, gamepad, display, audio);
+                    }
+                    // Clear any fade set by the gameplay rendering
+                    display.fade = color::INVISIBLE;
+                }
+                Window::NewGame => {
+                    if top_level {
+                        game_update_result = new_game::process(state, ui, settings, display, audio);
+                    }
+                    // Clear any fade set by the gameplay rendering
+                    display.fade = color::INVISIBLE;
+                }
                 Window::Endgame => {
                     display.fade = color::INVISIBLE;
                     if top_level {
@@ -518,9 +613,82 @@ pub fn update(
         }
     }
 
+    // NOTE: Keep re-simulating without rendering until we reach the
+    // seek target. We do render every `REPLAY_SEEK_FRAME_TICKS` so the
+    // window doesn't freeze during a long seek.
+    if let Some(seek) = state.replay_seek {
+        if std::matches!(game_update_result, RunningState::Running)
+            && state.tick_id < seek.target_tick
+            && state.tick_id % REPLAY_SEEK_FRAME_TICKS != 0
+        {
+            return RunningState::Skip;
+        }
+    }
+
+    // NOTE: a sped up replay only renders every few ticks.
+    if state.replay
+        && state.replay_seek.is_none()
+        && !state.paused
+        && state.window_stack.top() == Window::Game
+        && std::matches!(game_update_result, RunningState::Running)
+        && state.tick_id % state.replay_speed.ticks_per_frame() != 0
+    {
+        return RunningState::Skip;
+    }
+
     game_update_result
 }
 
+/// A slowed down replay keeps every tick on the screen for several
+/// frames. The frames in between behave as if the replay was paused.
+fn advance_replay_tick_frames(state: &mut State) {
+    if state.replay_seek.is_none() && !state.paused {
+        state.replay_tick_frames =
+            (state.replay_tick_frames + 1) % state.replay_speed.frames_per_tick();
+    }
+}
+
+/// The time that passes during this frame.
+///
+/// NOTE: the timers must not move while a slowed down replay is
+/// holding a tick. E.g. the game waits for the screen fade to finish
+/// so the replay would diverge otherwise.
+fn frame_dt(state: &State, dt: Duration) -> Duration {
+    if state.replay_tick_frames > 0 {
+        Duration::ZERO
+    } else {
+        dt
+    }
+}
+
+/// Seek the replay based on the arrow keys:
+///
+/// * Left / Right: go one tick back or forward
+/// * Shift + Left / Right: jump `REPLAY_SEEK_JUMP_TICKS` back or forward
+fn process_replay_seek_keys(state: &mut State, keys: &mut Keys) {
+    let current_tick = match state.replay_seek {
+        Some(seek) => seek.target_tick,
+        None => state.tick_id,
+    };
+    let target_tick = if keys.matches(|k| k.shift && k.code == KeyCode::Left) {
+        Some(current_tick - REPLAY_SEEK_JUMP_TICKS)
+    } else if keys.matches(|k| k.shift && k.code == KeyCode::Right) {
+        Some(current_tick + REPLAY_SEEK_JUMP_TICKS)
+    } else if keys.matches_code(KeyCode::Left) {
+        Some(current_tick - 1)
+    } else if keys.matches_code(KeyCode::Right) {
+        Some(current_tick + 1)
+    } else {
+        None
+    };
+
+    if let Some(target_tick) = target_tick {
+        if let Err(err) = state.seek_replay(target_tick) {
+            log::error!("Could not seek the replay to tick {}: {}", target_tick, err);
+        }
+    }
+}
+
 fn enqueue_background_music(audio: &mut Audio, audio_rng: &mut Random) {
     if audio.background_sound_queue.len() <= 1 {
         let sound = if cfg!(feature = "recording") {
@@ -563,7 +731,9 @@ fn process_game(
         highlighted_tiles.push(pos);
     }
 
-    if !active {
+    // NOTE: while a slowed down replay holds a tick, only show it.
+    // Anything else would happen more than once per tick.
+    if !active || state.replay_tick_frames > 0 {
         return RunningState::Running;
     }
 
@@ -642,6 +812,7 @@ fn process_game(
     }
 
     let player_was_alive = state.player.alive();
+    let game_was_in_progress = state.game_session == GameSession::InProgress;
 
     // Uncover map / set the Cheat mode
     if cfg!(feature = "cheating") && state.keys.matches_code(KeyCode::F6) {
@@ -675,9 +846,6 @@ fn process_game(
         place_victory_npc(state);
     }
 
-    // TODO: NOTE: this now doesn't work on replays because state.keys only contains the replay keys
-    let paused_one_step = state.paused && state.keys.matches_code(KeyCode::Right);
-
     // Animation to re-center the screen around the player when they
     // get too close to an edge.
     state.pos_timer.update(dt);
@@ -696,7 +864,9 @@ fn process_game(
 
     let simulation_area = formula::simulation_area(state.player.pos);
 
-    if (!state.paused || paused_one_step) && state.side != Side::Victory {
+    // NOTE: a paused replay is stepped through by seeking one tick
+    // forward. See `process_replay_seek_keys`.
+    if !state.replay_paused() && state.side != Side::Victory {
         let monster_count = state.world.monsters(simulation_area).count();
         let monster_with_ap_count = state
             .world
@@ -736,6 +906,18 @@ fn process_game(
             state.path_walking_timer.update(dt);
         }
 
+        state.autoplay_timer.update(dt);
+        let bot_can_act = state.game_session == GameSession::InProgress
+            && state.player.alive()
+            && state.player.has_ap(1)
+            && state.commands.is_empty()
+            && state.explosion_animation.is_none();
+        if state.autoplay && !state.replay && bot_can_act && state.autoplay_timer.finished() {
+            state.autoplay_timer.reset();
+            let command = autoplay::next_command(state);
+            state.commands.push_back(command);
+        }
+
         // NOTE: Show the path from the player to the mouse pointer
         let mouse_inside_map =
             state.mouse.tile_pos >= (0, 0) && state.mouse.tile_pos < state.map_size;
@@ -808,69 +990,210 @@ fn process_game(
         let player_ap = state.player.ap();
         log::debug!("Player AP before processing: {player_ap}");
         if state.player.ap() >= 1 {
-            process_player(state, display, audio, simulation_area);
-        }
-        let player_took_action = player_ap > state.player.ap();
-        let monsters_can_move = state.player.ap() == 0 || player_took_action;
-        log::debug!("Player AP: {player_ap}, Player took action: {player_took_action}, Monsters can move: {monsters_can_move}");
-
-        if state.explosion_animation.is_none() {
-            if monsters_can_move {
-                process_monsters(
-                    &mut state.world,
-                    &mut state.player,
-                    simulation_area,
-                    display.tile_size,
-                    &mut state.rng,
-                    &mut state.audio_rng,
-                    audio,
-                    &state.palette,
-                    &mut state.extra_animations,
-                );
-            } else {
-                log::debug!("Monsters waiting for player.");
+            let commands_executed = state.commands_executed;
+            process_player(state, display.tile_size, audio, simulation_area);
+            let player_idle = state.commands_executed == commands_executed;
+            if state.replay_error.is_some() || (player_idle && replay_ran_out_of_commands(state)) {
+                return RunningState::Stopped;
             }
+        }
+        entire_turn_ended =
+            process_monsters_turn(state, audio, display.tile_size, simulation_area, player_ap);
+    }
+
+    finish_tick(
+        state,
+        audio,
+        simulation_area,
+        entire_turn_ended,
+        player_was_alive,
+        dt,
+    );
+
+    // NOTE: autosave after the first turn too. Otherwise a crash
+    // early on would offer to recover the previous game's autosave.
+    if entire_turn_ended
+        && settings.autosave_turns > 0
+        && (state.turn == 1 || state.turn % settings.autosave_turns == 0)
+    {
+        autosave(state);
+    }
+    if game_was_in_progress && state.game_session == GameSession::Ended && !state.replay {
+        save::Slots::in_data_dir().remove_autosaves();
+    }
+
+    let explored = state
+        .world
+        .cell(state.mouse_world_position())
+        .map_or(true, |cell| cell.explored);
+
+    let mouse_window_pos_px = state.mouse.screen_pos;
+    let window_size_px = display.screen_size_px;
+    let sidebar_width_px = formula::sidebar_width_px(display.text_size);
+    let game_area_px = Point::new(window_size_px.x - sidebar_width_px, window_size_px.y);
+
+    // NOTE: Is the mouse pointer inside the actual game map? Otherwise it's in the sidebar or something.
+    let pointer_inside_game_area = mouse_window_pos_px.x >= 0
+        && mouse_window_pos_px.y >= 0
+        && mouse_window_pos_px.x <= game_area_px.x
+        && mouse_window_pos_px.y <= game_area_px.y;
+
+    // NOTE: show tooltip of a hovered-over object
+    let tooltip = if !explored && settings.hide_unseen_tiles || !pointer_inside_game_area {
+        None
+    } else if state.mouse_world_position() == state.player.pos {
+        Some("Player Character")
+    } else if let Some(monster) = state.world.monster_on_pos(state.mouse_world_position()) {
+        Some(monster.name())
+    } else if let Some(cell) = state.world.cell(state.mouse_world_position()) {
+        cell.items.first().map(|item| item.kind.name())
+    } else {
+        None
+    };
+    if let Some(tooltip) = tooltip {
+        // NOTE: only show tooltips when we're not scrolling the screen.
+        // It looks bad otherwise.
+        if state.pos_timer.finished() {
+            egui::show_tooltip_text(ui.ctx(), egui::Id::new("Tile Tooltip"), tooltip);
+        }
+    }
+
+    // NOTE: re-centre the display if the player reached the end of the screen
+    let no_left_mouse = !state.mouse.left_is_down && !state.mouse.left_clicked;
+    if state.pos_timer.finished() && no_left_mouse {
+        let display_pos = state.screen_pos_from_world_pos(state.player.pos);
+        // NOTE: this is the re-center speed. We calculate it based on
+        // the map size. That way the speed itself remains more or
+        // less constant.
+        let scroll_rate_ms_per_tile = 14;
+        let max_display_size = std::cmp::max(state.map_size.x, state.map_size.y) as u64;
+        let ms = if state.replay_full_speed {
+            100
+        } else {
+            scroll_rate_ms_per_tile * max_display_size
+        };
+        let dur = Duration::from_millis(ms);
+        let exploration_radius = formula::exploration_radius(state.player.mind);
+        // TODO: move the screen roughly the same distance along X and Y
+        if display_pos.x < exploration_radius
+            || display_pos.x >= state.map_size.x - exploration_radius
+            || display_pos.y < exploration_radius
+            || display_pos.y >= state.map_size.y - exploration_radius
+        {
+            state.pos_timer = Timer::new(dur);
+            state.old_screen_pos = state.screen_position_in_world;
+            // change the screen centre to that of the player
+            state.new_screen_pos = state.player.pos;
         } else {
-            log::debug!("Monster's waiting for the explosion to end.");
+            // Do nothing
         }
+    }
+    // Hide the keyboard movement hints if the player gets too close
+    {
+        // NOTE: this is no longer having any effect. Hints are disabled in `State::new`.
 
-        // NOTE: the anxiety counter bar is hidden at the start, but
-        // we want to show it as soon as it increases.
-        if player_took_action && !state.player.anxiety_counter.is_min() {
-            state.show_anxiety_counter = true;
+        let player_screen_pos = state.screen_pos_from_world_pos(state.player.pos);
+        let d = 10;
+        if player_screen_pos.x < d
+            || player_screen_pos.y < d
+            || state.map_size.x - player_screen_pos.x < d
+            || state.map_size.y - player_screen_pos.y < d
+        {
+            state.show_keyboard_movement_hints = false;
         }
 
-        if player_took_action && state.player.mind.is_high() {
-            if let Some(victory_npc_id) = state.victory_npc_id.take() {
-                log::info!("Player got High, the Victory NPC disappears!");
-                if let Some(vnpc) = state.world.monster_mut(victory_npc_id) {
-                    // TODO: move this (and other init stuff from
-                    // Monster::new) to custom functions?
-                    vnpc.kind = monster::Kind::Signpost;
-                    vnpc.behavior = ai::Behavior::Immobile;
-                    vnpc.ai_state = ai::AIState::NoOp
-                }
-            }
+        if cfg!(feature = "recording") {
+            state.show_keyboard_movement_hints = false;
         }
+    }
 
-        // Reset all action points only after everyone is at zero:
-        let player_turn_ended = !state.player.has_ap(1);
-        let monster_turn_ended = state
-            .world
-            .monsters(simulation_area)
-            .filter(|m| m.has_ap(1))
-            .count()
-            == 0;
+    // NOTE: Remove any animations that are already finished.
+    state.extra_animations.retain(|a| a.animation.in_progress());
+    RunningState::Running
+}
 
-        entire_turn_ended = player_turn_ended && monster_turn_ended;
-        log::debug!(
-            "Entire turn ended: {}, player turn ended: {}, monster turn ended: {}",
-            entire_turn_ended,
-            player_turn_ended,
-            monster_turn_ended
-        );
+/// Let the monsters act once the player's done (or has no action
+/// points left). Returns whether the entire turn has ended.
+pub(crate) fn process_monsters_turn(
+    state: &mut State,
+    audio: &mut Audio,
+    tile_size: i32,
+    simulation_area: Rectangle,
+    player_ap: i32,
+) -> bool {
+    let player_took_action = player_ap > state.player.ap();
+    let monsters_can_move = state.player.ap() == 0 || player_took_action;
+    log::debug!("Player AP: {player_ap}, Player took action: {player_took_action}, Monsters can move: {monsters_can_move}");
+
+    if state.explosion_animation.is_none() {
+        if monsters_can_move {
+            process_monsters(
+                &mut state.world,
+                &mut state.player,
+                simulation_area,
+                tile_size,
+                &mut state.rng,
+                &mut state.audio_rng,
+                audio,
+                &state.palette,
+                &mut state.extra_animations,
+            );
+        } else {
+            log::debug!("Monsters waiting for player.");
+        }
+    } else {
+        log::debug!("Monster's waiting for the explosion to end.");
+    }
+
+    // NOTE: the anxiety counter bar is hidden at the start, but
+    // we want to show it as soon as it increases.
+    if player_took_action && !state.player.anxiety_counter.is_min() {
+        state.show_anxiety_counter = true;
     }
 
+    if player_took_action && state.player.mind.is_high() {
+        if let Some(victory_npc_id) = state.victory_npc_id.take() {
+            log::info!("Player got High, the Victory NPC disappears!");
+            if let Some(vnpc) = state.world.monster_mut(victory_npc_id) {
+                // TODO: move this (and other init stuff from
+                // Monster::new) to custom functions?
+                vnpc.kind = monster::Kind::Signpost;
+                vnpc.behavior = ai::Behavior::Immobile;
+                vnpc.ai_state = ai::AIState::NoOp
+            }
+        }
+    }
+
+    // Reset all action points only after everyone is at zero:
+    let player_turn_ended = !state.player.has_ap(1);
+    let monster_turn_ended = state
+        .world
+        .monsters(simulation_area)
+        .filter(|m| m.has_ap(1))
+        .count()
+        == 0;
+
+    let entire_turn_ended = player_turn_ended && monster_turn_ended;
+    log::debug!(
+        "Entire turn ended: {}, player turn ended: {}, monster turn ended: {}",
+        entire_turn_ended,
+        player_turn_ended,
+        monster_turn_ended
+    );
+    entire_turn_ended
+}
+
+/// Start a new turn if the previous one ended and resolve the dose
+/// explosions and the player's death. Returns the cause of death if
+/// the player died during this tick.
+pub(crate) fn finish_tick(
+    state: &mut State,
+    audio: &mut Audio,
+    simulation_area: Rectangle,
+    entire_turn_ended: bool,
+    player_was_alive: bool,
+    dt: Duration,
+) -> Option<CauseOfDeath> {
     // Reset the player & monster action points
     // NOTE: doing this only after we've logged the validations. Actually maybe we want to do this
     // before we start turn processing??
@@ -884,6 +1207,7 @@ fn process_game(
 
     if entire_turn_ended {
         log::debug!("Turn {} has ended.", state.turn);
+        state.player.run_stats.record_turn(state.player.mind);
         state.turn += 1;
     }
 
@@ -898,7 +1222,9 @@ fn process_game(
     if let Some(ref anim) = state.explosion_animation {
         for (pos, _, effect) in anim.tiles() {
             if effect.contains(animation::TileEffect::KILL) {
-                kill_monster(pos, &mut state.world, audio);
+                if let Some(kind) = kill_monster(pos, &mut state.world, audio) {
+                    state.player.run_stats.record_kill(kind);
+                }
             }
             if effect.contains(animation::TileEffect::SHATTER) {
                 if let Some(cell) = state.world.cell_mut(pos) {
@@ -911,12 +1237,13 @@ fn process_game(
     }
 
     // Set the fadeout animation on death
+    let mut cause_of_death = None;
     if player_was_alive && !state.player.alive() {
         use crate::player::CauseOfDeath::*;
         log::info!("Player died.");
 
         audio.mix_sound_effect(Effect::GameOver, Duration::from_millis(0));
-        let cause_of_death = formula::cause_of_death(&state.player);
+        cause_of_death = formula::cause_of_death(&state.player);
         let fade_color = if cfg!(feature = "recording") {
             state.palette.fade_to_black_animation
         } else {
@@ -968,42 +1295,6 @@ fn process_game(
         ));
     }
 
-    let explored = state
-        .world
-        .cell(state.mouse_world_position())
-        .map_or(true, |cell| cell.explored);
-
-    let mouse_window_pos_px = state.mouse.screen_pos;
-    let window_size_px = display.screen_size_px;
-    let sidebar_width_px = formula::sidebar_width_px(display.text_size);
-    let game_area_px = Point::new(window_size_px.x - sidebar_width_px, window_size_px.y);
-
-    // NOTE: Is the mouse pointer inside the actual game map? Otherwise it's in the sidebar or something.
-    let pointer_inside_game_area = mouse_window_pos_px.x >= 0
-        && mouse_window_pos_px.y >= 0
-        && mouse_window_pos_px.x <= game_area_px.x
-        && mouse_window_pos_px.y <= game_area_px.y;
-
-    // NOTE: show tooltip of a hovered-over object
-    let tooltip = if !explored && settings.hide_unseen_tiles || !pointer_inside_game_area {
-        None
-    } else if state.mouse_world_position() == state.player.pos {
-        Some("Player Character")
-    } else if let Some(monster) = state.world.monster_on_pos(state.mouse_world_position()) {
-        Some(monster.name())
-    } else if let Some(cell) = state.world.cell(state.mouse_world_position()) {
-        cell.items.first().map(|item| item.kind.name())
-    } else {
-        None
-    };
-    if let Some(tooltip) = tooltip {
-        // NOTE: only show tooltips when we're not scrolling the screen.
-        // It looks bad otherwise.
-        if state.pos_timer.finished() {
-            egui::show_tooltip_text(ui.ctx(), egui::Id::new("Tile Tooltip"), tooltip);
-        }
-    }
-
     // NOTE: update the dose/food explosion animations
     state.explosion_animation = state.explosion_animation.take().and_then(|mut animation| {
         animation.update(dt);
@@ -1014,58 +1305,7 @@ fn process_game(
         }
     });
 
-    // NOTE: re-centre the display if the player reached the end of the screen
-    let no_left_mouse = !state.mouse.left_is_down && !state.mouse.left_clicked;
-    if state.pos_timer.finished() && no_left_mouse {
-        let display_pos = state.screen_pos_from_world_pos(state.player.pos);
-        // NOTE: this is the re-center speed. We calculate it based on
-        // the map size. That way the speed itself remains more or
-        // less constant.
-        let scroll_rate_ms_per_tile = 14;
-        let max_display_size = std::cmp::max(state.map_size.x, state.map_size.y) as u64;
-        let ms = if state.replay_full_speed {
-            100
-        } else {
-            scroll_rate_ms_per_tile * max_display_size
-        };
-        let dur = Duration::from_millis(ms);
-        let exploration_radius = formula::exploration_radius(state.player.mind);
-        // TODO: move the screen roughly the same distance along X and Y
-        if display_pos.x < exploration_radius
-            || display_pos.x >= state.map_size.x - exploration_radius
-            || display_pos.y < exploration_radius
-            || display_pos.y >= state.map_size.y - exploration_radius
-        {
-            state.pos_timer = Timer::new(dur);
-            state.old_screen_pos = state.screen_position_in_world;
-            // change the screen centre to that of the player
-            state.new_screen_pos = state.player.pos;
-        } else {
-            // Do nothing
-        }
-    }
-    // Hide the keyboard movement hints if the player gets too close
-    {
-        // NOTE: this is no longer having any effect. Hints are disabled in `State::new`.
-
-        let player_screen_pos = state.screen_pos_from_world_pos(state.player.pos);
-        let d = 10;
-        if player_screen_pos.x < d
-            || player_screen_pos.y < d
-            || state.map_size.x - player_screen_pos.x < d
-            || state.map_size.y - player_screen_pos.y < d
-        {
-            state.show_keyboard_movement_hints = false;
-        }
-
-        if cfg!(feature = "recording") {
-            state.show_keyboard_movement_hints = false;
-        }
-    }
-
-    // NOTE: Remove any animations that are already finished.
-    state.extra_animations.retain(|a| a.animation.in_progress());
-    RunningState::Running
+    cause_of_death
 }
 
 fn process_monsters(
@@ -1256,23 +1496,23 @@ fn process_player_action(
     tile_size: i32,
     palette: &Palette,
     audio: &mut Audio,
-) {
+) -> Option<Command> {
     log::debug!("Processing player action");
     if !player.alive() {
         log::debug!("Processing player action, but the player is dead.");
-        return;
+        return None;
     }
     if !player.has_ap(1) {
         log::debug!(
             "Processing player action, but the player has no AP: {}",
             player.ap()
         );
-        return;
+        return None;
     }
 
     if let Some(command) = commands.pop_front() {
         log::debug!("Player Command: {:?}", command);
-        let mut action = match command {
+        let mut action = match command.clone() {
             Command::N => Action::Move(player.pos + (0, -1)),
             Command::S => Action::Move(player.pos + (0, 1)),
             Command::W => Action::Move(player.pos + (-1, 0)),
@@ -1290,7 +1530,7 @@ fn process_player_action(
             Command::UseStrongDose => Action::Use(item::Kind::StrongDose),
 
             // NOTE: ignore, this has been processed elsewhere
-            Command::WalkPath => return,
+            Command::WalkPath => return None,
 
             Command::ShowMessageBox {
                 ttl,
@@ -1298,7 +1538,7 @@ fn process_player_action(
                 message,
             } => {
                 window_stack.push(window::timed_message_box(title, message, ttl));
-                return;
+                return Some(command);
             }
         };
         log::debug!("Action from Command: {:?}", action);
@@ -1415,6 +1655,12 @@ fn process_player_action(
                                 for npc in npcs {
                                     if npc.position == dest {
                                         log::info!("NPC {} accompanies the player.", npc);
+                                        if !npc.accompanying_player {
+                                            player
+                                                .run_stats
+                                                .companions_met
+                                                .extend(npc.companion_bonus);
+                                        }
                                         npc.accompanying_player = true;
                                         assert!(npc.companion_bonus.is_some());
                                     } else if npc.accompanying_player {
@@ -1434,7 +1680,9 @@ fn process_player_action(
 
                             _ => {}
                         }
-                        kill_monster(dest, world, audio);
+                        if let Some(kind) = kill_monster(dest, world, audio) {
+                            player.run_stats.record_kill(kind);
+                        }
 
                         if kind.is_monster() {
                             *bumped_into_a_monster = true;
@@ -1481,6 +1729,7 @@ fn process_player_action(
                     audio.mix_sound_effect(Effect::Explosion, Duration::from_millis(0));
                     let food = player.inventory.remove(food_idx);
                     player.take_effect(food.modifier);
+                    player.run_stats.record_item_use(food.kind);
                     let food_explosion_radius = 2;
                     let animation = animation::SquareExplosion::new(
                         player.pos,
@@ -1544,14 +1793,16 @@ fn process_player_action(
                 unreachable!();
             }
         }
+        Some(command)
     } else {
         log::debug!("No Command found");
+        None
     }
 }
 
-fn process_player(
+pub(crate) fn process_player(
     state: &mut State,
-    display: &Display,
+    tile_size: i32,
     audio: &mut Audio,
     simulation_area: Rectangle,
 ) {
@@ -1648,7 +1899,7 @@ fn process_player(
 
     log::debug!("Commands: {:?}", state.commands);
     let previous_action_points = state.player.ap();
-    process_player_action(
+    let executed_command = process_player_action(
         &mut state.player,
         &mut state.commands,
         &mut state.world,
@@ -1657,12 +1908,16 @@ fn process_player(
         &mut state.rng,
         &mut state.window_stack,
         &mut state.player_bumped_into_a_monster,
-        display.tile_size,
+        tile_size,
         &state.palette,
         audio,
     );
     log::debug!("player action processed");
 
+    if let Some(command) = executed_command {
+        record_command(state, command);
+    }
+
     // If the player ever picks up a dose, mark it in this variable:
     let player_picked_up_a_dose = state.player.inventory.iter().any(item::Item::is_dose);
     if player_picked_up_a_dose {
@@ -1837,19 +2092,29 @@ pub fn inventory_key(kind: item::Kind) -> u8 {
     unreachable!()
 }
 
-fn kill_monster(monster_position: Point, world: &mut World, audio: &mut Audio) {
+/// Kill the monster at the given position and return its kind. If
+/// there's no monster there or it's invincible, nothing happens.
+fn kill_monster(
+    monster_position: Point,
+    world: &mut World,
+    audio: &mut Audio,
+) -> Option<monster::Kind> {
     let invincible = world
         .monster_on_pos(monster_position)
         .is_some_and(|m| m.invincible);
     if invincible {
         // It's invincible: no-op
+        None
     } else {
+        let mut killed = None;
         if let Some(monster) = world.monster_on_pos(monster_position) {
             log::debug!("Killing monster: {:?}", monster);
             monster.dead = true;
+            killed = Some(monster.kind);
             audio.mix_sound_effect(Effect::MonsterHit, Duration::from_millis(0));
         }
         world.remove_monster(monster_position);
+        killed
     }
 }
 
@@ -1867,6 +2132,7 @@ fn use_dose(
     if let Intoxication { state_of_mind, .. } = item.modifier {
         let radius = if state_of_mind <= 100 { 4 } else { 6 };
         player.take_effect(item.modifier);
+        player.run_stats.record_item_use(item.kind);
         let animation: Box<dyn AreaOfEffect> = match item.kind {
             Dose | StrongDose => Box::new(animation::SquareExplosion::new(
                 player.pos,
@@ -1927,114 +2193,207 @@ fn show_exit_stats(stats: &Stats) {
     log::info!("Lowest FPS: {}", stats.lowest_fps());
 }
 
-fn verify_states(expected: &state::Verification, actual: &state::Verification) {
-    let mut valid = true;
+/// The player is waiting for a command, but the command stream
+/// replay doesn't have any left.
+fn replay_ran_out_of_commands(state: &mut State) -> bool {
+    let ran_out = state.replay
+        && state.replay_stream() == ReplayStream::Commands
+        && state.commands_executed as usize >= state.recorded_commands.len()
+        && state.commands.is_empty()
+        && state.explosion_animation.is_none()
+        && state.player.alive()
+        && state.side == Side::Player;
+    if ran_out {
+        if state.replay_ticks.is_some() {
+            log::warn!(
+                "The replay ran out of commands at tick {}, stopping.",
+                state.tick_id
+            );
+        } else {
+            log::error!("The replay ran out of commands at tick {}", state.tick_id);
+            state.replay_error = Some(ReplayError::MissingInput {
+                tick_id: state.tick_id,
+            });
+        }
+    }
+    ran_out
+}
 
-    if expected.tick_id != actual.tick_id {
-        log::error!(
-            "Expected tick_id: {}, actual: {}",
-            expected.tick_id,
-            actual.tick_id
-        );
-        valid = false;
+/// Log the command the player has just carried out. When playing a
+/// command stream back, check the game still matches the recording.
+fn record_command(state: &mut State, command: Command) {
+    state.commands_executed += 1;
+    let verification = state.command_verification();
+
+    if state.replay && state.replay_stream() == ReplayStream::Commands {
+        let index = state.commands_executed as usize - 1;
+        if let Some(recorded) = state.recorded_commands.get(index) {
+            if let Some(replay_ticks) = &mut state.replay_ticks {
+                replay_ticks.push(state::ReplayTick {
+                    actual: verification.clone(),
+                    recorded: recorded.verification.clone(),
+                });
+            } else if let Some(desync) = recorded.verification.desync(&verification) {
+                log_desync(&desync);
+                state.replay_error = Some(ReplayError::Desync(Box::new(desync)));
+            }
+        }
     }
 
-    if expected.turn != actual.turn {
-        log::error!("Expected turn: {}, actual: {}", expected.turn, actual.turn);
-        valid = false;
+    let mut replay_command = ReplayCommand {
+        turn: state.turn,
+        command,
+        verification: VerificationWrapper::None,
+    };
+    if cfg!(feature = "verifications") {
+        replay_command.verification = if state.debug {
+            VerificationWrapper::Verification(verification)
+        } else {
+            VerificationWrapper::Hash(*verification.hash().as_bytes())
+        };
     }
+    state::log_command(&mut state.command_logger, &replay_command);
+}
 
-    if expected.chunk_count != actual.chunk_count {
-        log::error!(
-            "Expected chunks: {}, actual: {}",
-            expected.chunk_count,
-            actual.chunk_count
-        );
-        valid = false;
+fn log_desync(desync: &Desync) {
+    log::error!("Validation failed!");
+    match desync {
+        Desync::Verification { diff, .. } => log_verification_diff(diff),
+        Desync::Hash {
+            tick_id,
+            expected_hash,
+            actual,
+        } => {
+            log::error!(
+                "Tick {}: expected hash: {}, actual: {}",
+                tick_id,
+                expected_hash,
+                actual.hash().to_hex()
+            );
+        }
     }
+}
 
-    if expected.player_pos != actual.player_pos {
-        log::error!(
-            "Expected player position: {}, actual: {}",
-            expected.player_pos,
-            actual.player_pos
-        );
-        valid = false;
+fn log_verification_diff(diff: &state::VerificationDiff) {
+    if let Some((expected, actual)) = diff.tick_id {
+        log::error!("Expected tick_id: {}, actual: {}", expected, actual);
     }
 
-    if expected.monsters.len() != actual.monsters.len() {
-        log::error!(
-            "Expected monster count: {}, actual: {}",
-            expected.monsters.len(),
-            actual.monsters.len()
-        );
-        valid = false;
+    if let Some((expected, actual)) = diff.turn {
+        log::error!("Expected turn: {}, actual: {}", expected, actual);
     }
 
-    if expected.monsters != actual.monsters {
-        let expected_monsters: HashMap<Point, (Point, monster::Kind)> = expected
-            .monsters
-            .iter()
-            .map(|&(pos, chunk_pos, monster)| (pos, (chunk_pos, monster)))
-            .collect();
-        let actual_monsters: HashMap<Point, (Point, monster::Kind)> = actual
-            .monsters
-            .iter()
-            .map(|&(pos, chunk_pos, monster)| (pos, (chunk_pos, monster)))
-            .collect();
-
-        for (pos, expected) in &expected_monsters {
-            match actual_monsters.get(pos) {
-                Some(actual) => {
-                    if expected != actual {
-                        log::error!(
-                            "Monster at {} differ. Expected: {:?}, \
-                             actual: {:?}",
-                            pos,
-                            expected,
-                            actual
-                        );
-                        valid = false;
-                    }
-                }
-                None => {
-                    log::error!(
-                        "Monster expected at {}: {:?}, but it's not \
-                         there.",
-                        pos,
-                        expected
-                    );
-                    valid = false;
-                }
-            }
-        }
+    if let Some((expected, actual)) = diff.chunk_count {
+        log::error!("Expected chunks: {}, actual: {}", expected, actual);
+    }
+
+    if let Some((expected, actual)) = diff.player_pos {
+        log::error!("Expected player position: {}, actual: {}", expected, actual);
+    }
 
-        for (pos, actual) in &actual_monsters {
-            if !expected_monsters.contains_key(pos) {
-                log::error!("There is an unexpected monster at: {}: {:?}.", pos, actual);
-                valid = false;
+    if let Some((expected, actual)) = diff.monster_count {
+        log::error!("Expected monster count: {}, actual: {}", expected, actual);
+    }
+
+    for monster in &diff.monsters {
+        match (monster.expected, monster.actual) {
+            (Some(expected), Some(actual)) => {
+                log::error!(
+                    "Monster at {} differ. Expected: {:?}, \
+                     actual: {:?}",
+                    monster.pos,
+                    expected,
+                    actual
+                );
             }
+            (Some(expected), None) => {
+                log::error!(
+                    "Monster expected at {}: {:?}, but it's not \
+                     there.",
+                    monster.pos,
+                    expected
+                );
+            }
+            (None, Some(actual)) => {
+                log::error!(
+                    "There is an unexpected monster at: {}: {:?}.",
+                    monster.pos,
+                    actual
+                );
+            }
+            (None, None) => {}
         }
     }
+}
 
-    // Use if+panic rather than `assert_eq`. The latter prints out both objects and they can be MASSIVE.
-    // We've got a more targeted expected/actual printout above so all we need here is to crash.
-    #[allow(clippy::panic)]
-    if !valid {
-        panic!("Validation failed!");
+/// Save the game into the next autosave slot so it can be recovered
+/// if the game crashes. Does nothing unless there's a game in
+/// progress that the player is playing.
+pub fn autosave(state: &State) {
+    if state.replay
+        || state.autoplay
+        || state.game_session != GameSession::InProgress
+        || !state.player.alive()
+    {
+        return;
     }
+    log::info!("Autosaving on turn {}", state.turn);
+    if let Err(error) = save::Slots::in_data_dir().autosave(state) {
+        log::error!("Error autosaving the game: {:?}", error);
+    }
+}
+
+pub fn create_new_game_state(state: &State, settings: &Settings) -> State {
+    let game = ShareCode {
+        seed: util::random_seed(),
+        challenge: settings.challenge(),
+        // NOTE: keep the landscape the game was started with.
+        landscape: state.world.landscape(),
+    };
+    new_game_state(state, settings, state.replay_stream(), game)
+}
+
+/// A new game with the seed, challenge and landscape of `game`. This
+/// is how the player starts a game somebody shared with them or the
+/// Daily Run.
+pub fn create_shared_game_state(state: &State, settings: &Settings, game: ShareCode) -> State {
+    new_game_state(state, settings, state.replay_stream(), game)
 }
 
-pub fn create_new_game_state(state: &State, new_challenge: Challenge) -> State {
+/// A new game played by the `autoplay` bot. It records the command
+/// stream because the bot doesn't press any keys that an input
+/// replay could play back.
+pub fn create_demo_game_state(state: &State, settings: &Settings) -> State {
+    let game = ShareCode {
+        seed: util::random_seed(),
+        challenge: settings.challenge(),
+        landscape: state.world.landscape(),
+    };
+    let mut state = new_game_state(state, settings, ReplayStream::Commands, game);
+    state.autoplay = true;
+    state
+}
+
+fn new_game_state(
+    state: &State,
+    settings: &Settings,
+    stream: ReplayStream,
+    game: ShareCode,
+) -> State {
     let mut state = State::new_game(
         state.world_size,
         state.map_size,
         state.panel_width,
         state.exit_after,
         state.debug,
+        false,
         state::generate_replay_path(),
-        new_challenge,
+        stream,
+        game.seed,
+        game.challenge,
+        game.landscape,
         state.palette,
+        state::DisplaySettings::from_settings(settings),
     );
     state.generate_world();
     state
@@ -2215,3 +2574,58 @@ fn walkable_place_nearby(
     // Radius `2` means the central point and the eight surrounding ones.
     point::SquareArea::new(pos, 2).find(|&point| world.walkable(point, blockers, player_pos))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{advance_replay_tick_frames, frame_dt};
+    use crate::{
+        generators::Landscape,
+        state::{Challenge, ReplaySpeed, State},
+    };
+
+    use std::time::Duration;
+
+    #[test]
+    fn slow_replay_holds_the_time() {
+        let dt = Duration::from_millis(16);
+        let mut state = State::new_session(1, Challenge::default(), Landscape::default());
+        state.replay = true;
+        for speed in [
+            ReplaySpeed::Quarter,
+            ReplaySpeed::Half,
+            ReplaySpeed::Normal,
+            ReplaySpeed::Double,
+        ] {
+            state.replay_speed = speed;
+            state.replay_tick_frames = 0;
+            let frames_per_tick = speed.frames_per_tick() as usize;
+            let dts: Vec<Duration> = (0..8)
+                .map(|_| {
+                    advance_replay_tick_frames(&mut state);
+                    frame_dt(&state, dt)
+                })
+                .collect();
+            for (frame, &frame_dt) in dts.iter().enumerate() {
+                let expected = if (frame + 1) % frames_per_tick == 0 {
+                    dt
+                } else {
+                    Duration::ZERO
+                };
+                assert_eq!(frame_dt, expected, "{} frame {}", speed, frame);
+            }
+        }
+    }
+
+    #[test]
+    fn paused_replay_keeps_the_tick_frames() {
+        let mut state = State::new_session(1, Challenge::default(), Landscape::default());
+        state.replay = true;
+        state.replay_speed = ReplaySpeed::Quarter;
+        advance_replay_tick_frames(&mut state);
+        assert_eq!(state.replay_tick_frames, 1);
+        state.paused = true;
+        advance_replay_tick_frames(&mut state);
+        assert_eq!(state.replay_tick_frames, 1);
+        assert_eq!(frame_dt(&state, Duration::from_millis(16)), Duration::ZERO);
+    }
+}
@@ -1,5 +1,136 @@
-use crate::{item::Item, level::Tile, monster::Monster, point::Point};
+use crate::{
+    formula, item::Item, level::Tile, monster::Monster, point::Point, random::Random,
+    state::Challenge,
+};
+
+use std::{error::Error, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
 
 pub mod forrest;
+pub mod urban;
 
 pub type GeneratedWorld = (Vec<(Point, Tile)>, Vec<Monster>, Vec<(Point, Item)>);
+
+/// Fills a newly created chunk with tiles, monsters and items.
+///
+/// The positions are relative to the chunk's top-left corner. Every
+/// decision that affects the gameplay must come from `rng` so the
+/// same seed always produces the same world. `throwaway_rng` is for
+/// the purely cosmetic ones (graphics, colours).
+///
+/// With the default `Region`, the chunk must come out the same as
+/// before the regions existed so the old replays keep working.
+pub trait WorldGenerator {
+    fn generate(
+        &self,
+        rng: &mut Random,
+        throwaway_rng: &mut Random,
+        size: Point,
+        player: Point,
+        challenge: Challenge,
+        region: Region,
+    ) -> GeneratedWorld;
+}
+
+/// Where in the world a chunk is. The generators use it to make
+/// parts of the world look different and get harder the further they
+/// are from the start.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Region {
+    /// `0.0` around the start, `1.0` at
+    /// `formula::REGION_HARDEST_DISTANCE` and beyond.
+    pub difficulty: f32,
+    /// Noise between `-1.0` (open) and `1.0` (overgrown). It changes
+    /// slowly so the neighbouring chunks have similar values.
+    pub biome: f32,
+}
+
+impl Region {
+    /// The region of the chunk whose centre is at `centre` (in world
+    /// coordinates).
+    pub fn new(world_seed: u32, centre: Point) -> Self {
+        let distance = ((centre.x as f32).powi(2) + (centre.y as f32).powi(2)).sqrt();
+        let difficulty = (distance - formula::REGION_EASY_DISTANCE)
+            / (formula::REGION_HARDEST_DISTANCE - formula::REGION_EASY_DISTANCE);
+        Self {
+            difficulty: difficulty.clamp(0.0, 1.0),
+            biome: biome_noise(world_seed, centre),
+        }
+    }
+}
+
+/// Value noise: random values at the corners of a grid of
+/// `formula::BIOME_SIZE` tiles, smoothly interpolated in between.
+fn biome_noise(world_seed: u32, pos: Point) -> f32 {
+    let size = formula::BIOME_SIZE;
+    let cell = Point::new(pos.x.div_euclid(size), pos.y.div_euclid(size));
+    let smoothstep = |t: f32| t * t * (3.0 - 2.0 * t);
+    let tx = smoothstep(pos.x.rem_euclid(size) as f32 / size as f32);
+    let ty = smoothstep(pos.y.rem_euclid(size) as f32 / size as f32);
+    let corner = |dx, dy| lattice_value(world_seed, cell + (dx, dy));
+    let top = crate::graphics::lerp_f32(corner(0, 0), corner(1, 0), tx);
+    let bottom = crate::graphics::lerp_f32(corner(0, 1), corner(1, 1), tx);
+    crate::graphics::lerp_f32(top, bottom, ty)
+}
+
+/// A value between `-1.0` and `1.0` that depends only on the seed
+/// and the grid point.
+fn lattice_value(world_seed: u32, point: Point) -> f32 {
+    // NOTE: the finaliser of MurmurHash3. It doesn't depend on the
+    // platform or the Rust version unlike `std::hash`.
+    let mut hash = world_seed
+        ^ (point.x as u32).wrapping_mul(0x9e37_79b1)
+        ^ (point.y as u32).wrapping_mul(0x85eb_ca77);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^= hash >> 16;
+    (hash as f32 / u32::MAX as f32) * 2.0 - 1.0
+}
+
+/// Which `WorldGenerator` creates the chunks of a world.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Landscape {
+    #[default]
+    Forest,
+    Urban,
+}
+
+pub const LANDSCAPE_NAMES: &[&str] = &["forest", "urban"];
+
+impl Landscape {
+    pub fn generator(self) -> &'static dyn WorldGenerator {
+        match self {
+            Landscape::Forest => &forrest::Forest,
+            Landscape::Urban => &urban::Urban,
+        }
+    }
+}
+
+impl FromStr for Landscape {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let landscape = match s {
+            "forest" => Landscape::Forest,
+            "urban" => Landscape::Urban,
+            _ => throw!(&format!(
+                "Unknown landscape: `{}`. Expected one of: {}",
+                s,
+                LANDSCAPE_NAMES.join(", ")
+            )),
+        };
+        Ok(landscape)
+    }
+}
+
+impl fmt::Display for Landscape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Landscape::Forest => f.write_str("forest"),
+            Landscape::Urban => f.write_str("urban"),
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     formula,
-    generators::GeneratedWorld,
+    generators::{GeneratedWorld, Region, WorldGenerator},
     graphic::Graphic,
     item::{self, Item},
     level::{Tile, TileKind},
@@ -20,6 +20,7 @@ fn generate_map(
     throwaway_rng: &mut Random,
     map_size: Point,
     player_pos: Point,
+    region: Region,
 ) -> Vec<(Point, Tile)> {
     assert!(formula::CHUNK_DENSITY_VARIABILITY.0 < formula::CHUNK_DENSITY_VARIABILITY.1);
     assert!(formula::CHUNK_BASELINE_DENSITY + formula::CHUNK_DENSITY_VARIABILITY.0 > 0.0);
@@ -30,7 +31,12 @@ fn generate_map(
             formula::CHUNK_DENSITY_VARIABILITY.0,
             formula::CHUNK_DENSITY_VARIABILITY.1,
             rng.rand_float(),
-        );
+        )
+        + region.biome * formula::BIOME_DENSITY_VARIABILITY;
+    let density = density.clamp(
+        formula::CHUNK_DENSITY_LIMITS.0,
+        formula::CHUNK_DENSITY_LIMITS.1,
+    );
     let occupied_count = (density * 100.0) as u32;
     let choices = [
         (TileKind::Empty, 100 - occupied_count),
@@ -112,20 +118,33 @@ fn generate_map(
     result
 }
 
-fn generate_monsters(
+pub fn generate_monsters(
     rng: &mut Random,
     map: &[(Point, Tile)],
     challenge: Challenge,
+    region: Region,
 ) -> Vec<Monster> {
     let monster_count = 5;
     let monster_chance = 30;
+    let per_monster = monster_chance / monster_count;
+    // NOTE: the open regions have more Anxieties, the overgrown ones
+    // more Depressions. The extra monsters in the harder regions are
+    // Shadows and Voices.
+    let biome_shift = (region.biome * per_monster as f32) as i32;
+    let extra = (region.difficulty * formula::REGION_EXTRA_MONSTER_CHANCE) as u32;
     let options = [
-        (None, 1000 - monster_chance),
-        (Some(Kind::Anxiety), monster_chance / monster_count),
-        (Some(Kind::Depression), monster_chance / monster_count),
-        (Some(Kind::Hunger), monster_chance / monster_count),
-        (Some(Kind::Shadows), monster_chance / monster_count),
-        (Some(Kind::Voices), monster_chance / monster_count),
+        (None, 1000 - monster_chance - extra),
+        (
+            Some(Kind::Anxiety),
+            (per_monster as i32 - biome_shift) as u32,
+        ),
+        (
+            Some(Kind::Depression),
+            (per_monster as i32 + biome_shift) as u32,
+        ),
+        (Some(Kind::Hunger), per_monster),
+        (Some(Kind::Shadows), per_monster + extra / 2),
+        (Some(Kind::Voices), per_monster + extra / 2),
         (Some(Kind::Npc), 2),
     ];
 
@@ -147,7 +166,8 @@ fn generate_monsters(
     result
 }
 
-fn new_item(kind: item::Kind, rng: &mut Random) -> Item {
+/// `purity` goes into the `state_of_mind` of the doses.
+fn new_item(kind: item::Kind, rng: &mut Random, purity: i32) -> Item {
     use crate::item::Kind::*;
     match kind {
         Dose => {
@@ -157,7 +177,7 @@ fn new_item(kind: item::Kind, rng: &mut Random) -> Item {
                 ..
             } = item.modifier
             {
-                *state_of_mind += formula::DOSE_MIND_VARIANCE.random(rng);
+                *state_of_mind += formula::DOSE_MIND_VARIANCE.random(rng) + purity;
             };
             item
         }
@@ -168,7 +188,7 @@ fn new_item(kind: item::Kind, rng: &mut Random) -> Item {
                 ..
             } = item.modifier
             {
-                *state_of_mind += formula::STRONG_DOSE_MIND_VARIANCE.random(rng);
+                *state_of_mind += formula::STRONG_DOSE_MIND_VARIANCE.random(rng) + purity;
             };
             item
         }
@@ -179,7 +199,7 @@ fn new_item(kind: item::Kind, rng: &mut Random) -> Item {
                 ..
             } = item.modifier
             {
-                *state_of_mind += formula::CARDINAL_DOSE_MIND_VARIANCE.random(rng);
+                *state_of_mind += formula::CARDINAL_DOSE_MIND_VARIANCE.random(rng) + purity;
             };
             item
         }
@@ -190,7 +210,7 @@ fn new_item(kind: item::Kind, rng: &mut Random) -> Item {
                 ..
             } = item.modifier
             {
-                *state_of_mind += formula::DIAGONAL_DOSE_MIND_VARIANCE.random(rng);
+                *state_of_mind += formula::DIAGONAL_DOSE_MIND_VARIANCE.random(rng) + purity;
             };
             item
         }
@@ -215,16 +235,23 @@ fn new_item(kind: item::Kind, rng: &mut Random) -> Item {
     }
 }
 
-fn generate_items(rng: &mut Random, map: &[(Point, Tile)]) -> Vec<(Point, Item)> {
+pub fn generate_items(
+    rng: &mut Random,
+    map: &[(Point, Tile)],
+    region: Region,
+) -> Vec<(Point, Item)> {
     use crate::item::Kind::*;
+    let food_portion = (1.0 + region.biome * formula::BIOME_FOOD_VARIABILITY)
+        * (1.0 - region.difficulty * formula::REGION_FOOD_PENALTY);
     let options = [
         (None, 1000),
         (Some(Dose), 8),
         (Some(StrongDose), 3),
         (Some(CardinalDose), 2),
         (Some(DiagonalDose), 2),
-        (Some(Food), 5),
+        (Some(Food), (5.0 * food_portion).round() as u32),
     ];
+    let purity = (region.difficulty * formula::REGION_DOSE_PURITY_BONUS) as i32;
 
     // NOTE: this calculates how many items we need to place. It
     // calculates the baseline number of empty tiles and the average
@@ -252,7 +279,7 @@ fn generate_items(rng: &mut Random, map: &[(Point, Tile)]) -> Vec<(Point, Item)>
             TileKind::Empty => {
                 let kind = *rng.choose_weighted(&options).unwrap_or(&None);
                 if let Some(kind) = kind {
-                    result.push((pos, new_item(kind, rng)));
+                    result.push((pos, new_item(kind, rng, purity)));
                     items_to_place -= 1;
                 }
             }
@@ -261,15 +288,24 @@ fn generate_items(rng: &mut Random, map: &[(Point, Tile)]) -> Vec<(Point, Item)>
     result
 }
 
-pub fn generate(
-    rng: &mut Random,
-    throwaway_rng: &mut Random,
-    size: Point,
-    player: Point,
-    challenge: Challenge,
-) -> GeneratedWorld {
-    let map = generate_map(rng, throwaway_rng, size, player);
-    let monsters = generate_monsters(rng, &map, challenge);
-    let items = generate_items(rng, &map);
-    (map, monsters, items)
+/// Trees scattered over open ground. This is the original world of
+/// the game.
+#[derive(Copy, Clone, Debug)]
+pub struct Forest;
+
+impl WorldGenerator for Forest {
+    fn generate(
+        &self,
+        rng: &mut Random,
+        throwaway_rng: &mut Random,
+        size: Point,
+        player: Point,
+        challenge: Challenge,
+        region: Region,
+    ) -> GeneratedWorld {
+        let map = generate_map(rng, throwaway_rng, size, player, region);
+        let monsters = generate_monsters(rng, &map, challenge, region);
+        let items = generate_items(rng, &map, region);
+        (map, monsters, items)
+    }
 }
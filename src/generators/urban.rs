@@ -0,0 +1,198 @@
+use crate::{
+    generators::{forrest, GeneratedWorld, Region, WorldGenerator},
+    graphic::Graphic,
+    level::{Tile, TileKind},
+    palette,
+    point::Point,
+    random::Random,
+    rect::Rectangle,
+    state::Challenge,
+};
+
+/// The distance between two parallel streets. The chunk size should
+/// be a multiple of this so the streets continue into the neighbouring
+/// chunks. Otherwise, the blocks cut off by the chunk's edge become
+/// parks.
+const BLOCK_SIZE: i32 = 16;
+
+/// The streets run along the top and left edge of every block. The
+/// world's origin is on a street so the player always starts there.
+const STREET_WIDTH: i32 = 3;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Lot {
+    Building,
+    /// Two buildings separated by a narrow alley.
+    Alley,
+    Park,
+}
+
+/// A grid of streets with walled buildings, alleys and small parks
+/// between them.
+#[derive(Copy, Clone, Debug)]
+pub struct Urban;
+
+impl WorldGenerator for Urban {
+    fn generate(
+        &self,
+        rng: &mut Random,
+        throwaway_rng: &mut Random,
+        size: Point,
+        player: Point,
+        challenge: Challenge,
+        region: Region,
+    ) -> GeneratedWorld {
+        let map = generate_map(rng, throwaway_rng, size, player);
+        let monsters = forrest::generate_monsters(rng, &map, challenge, region);
+        let items = forrest::generate_items(rng, &map, region);
+        (map, monsters, items)
+    }
+}
+
+fn generate_map(
+    rng: &mut Random,
+    throwaway_rng: &mut Random,
+    map_size: Point,
+    player_pos: Point,
+) -> Vec<(Point, Tile)> {
+    let width = map_size.x as usize;
+    let mut walls = vec![false; (map_size.x * map_size.y) as usize];
+    let mut build_wall = |pos: Point| walls[pos.y as usize * width + pos.x as usize] = true;
+
+    let lots = [(Lot::Building, 6), (Lot::Alley, 3), (Lot::Park, 1)];
+    for block_y in (0..map_size.y).step_by(BLOCK_SIZE as usize) {
+        for block_x in (0..map_size.x).step_by(BLOCK_SIZE as usize) {
+            let block_size = Point::new(
+                BLOCK_SIZE.min(map_size.x - block_x),
+                BLOCK_SIZE.min(map_size.y - block_y),
+            );
+            if block_size.x <= STREET_WIDTH || block_size.y <= STREET_WIDTH {
+                continue;
+            }
+            let lot = Rectangle::from_point_and_size(
+                Point::new(block_x + STREET_WIDTH, block_y + STREET_WIDTH),
+                Point::new(block_size.x - STREET_WIDTH, block_size.y - STREET_WIDTH),
+            );
+            let lot_kind = if block_size == Point::from_i32(BLOCK_SIZE) {
+                *rng.choose_weighted(&lots).unwrap_or(&Lot::Park)
+            } else {
+                Lot::Park
+            };
+            match lot_kind {
+                Lot::Building => generate_building(rng, lot, &mut build_wall),
+                Lot::Alley => {
+                    // NOTE: the alley is one tile wide and it splits
+                    // the lot either vertically or horizontally.
+                    let split = rng.range_inclusive(4, lot.size().x - 5);
+                    let (first, second) = if rng.range_inclusive(0, 1) == 0 {
+                        (
+                            Rectangle::from_point_and_size(
+                                lot.top_left(),
+                                Point::new(split, lot.size().y),
+                            ),
+                            Rectangle::from_point_and_size(
+                                lot.top_left() + (split + 1, 0),
+                                Point::new(lot.size().x - split - 1, lot.size().y),
+                            ),
+                        )
+                    } else {
+                        (
+                            Rectangle::from_point_and_size(
+                                lot.top_left(),
+                                Point::new(lot.size().x, split),
+                            ),
+                            Rectangle::from_point_and_size(
+                                lot.top_left() + (0, split + 1),
+                                Point::new(lot.size().x, lot.size().y - split - 1),
+                            ),
+                        )
+                    };
+                    generate_building(rng, first, &mut build_wall);
+                    generate_building(rng, second, &mut build_wall);
+                }
+                Lot::Park => {
+                    for pos in lot.points() {
+                        if rng.range_inclusive(0, 99) < 20 {
+                            build_wall(pos);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut result = vec![];
+    for y in 0..map_size.y {
+        for x in 0..map_size.x {
+            let pos = Point::new(x, y);
+            let wall = walls[y as usize * width + x as usize] && pos != player_pos;
+            let tile = if wall {
+                // NOTE: there's no graphic for a wall yet so the
+                // buildings are made out of trees of a single kind
+                // and colour.
+                let mut tile = Tile::new(TileKind::Tree);
+                tile.graphic = Graphic::Tree8;
+                tile.color_index = palette::TREE_COUNT - 1;
+                tile
+            } else {
+                let mut tile = Tile::new(TileKind::Empty);
+                tile.graphic = *throwaway_rng.choose_with_fallback(
+                    &[Graphic::Ground2, Graphic::Ground3, Graphic::Ground5],
+                    &Graphic::Ground2,
+                );
+                tile
+            };
+            result.push((pos, tile));
+        }
+    }
+    result
+}
+
+/// Put walls around the edge of `lot` with a single door in one of
+/// them.
+fn generate_building(rng: &mut Random, lot: Rectangle, build_wall: &mut impl FnMut(Point)) {
+    let top_left = lot.top_left();
+    let bottom_right = lot.bottom_right();
+    let door = match rng.range_inclusive(0, 3) {
+        0 => Point::new(
+            rng.range_inclusive(top_left.x + 1, bottom_right.x - 1),
+            top_left.y,
+        ),
+        1 => Point::new(
+            rng.range_inclusive(top_left.x + 1, bottom_right.x - 1),
+            bottom_right.y,
+        ),
+        2 => Point::new(
+            top_left.x,
+            rng.range_inclusive(top_left.y + 1, bottom_right.y - 1),
+        ),
+        _ => Point::new(
+            bottom_right.x,
+            rng.range_inclusive(top_left.y + 1, bottom_right.y - 1),
+        ),
+    };
+    for pos in lot.points() {
+        let edge = pos.x == top_left.x
+            || pos.x == bottom_right.x
+            || pos.y == top_left.y
+            || pos.y == bottom_right.y;
+        if edge && pos != door {
+            build_wall(pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_map, BLOCK_SIZE};
+    use crate::{point::Point, random::Random};
+
+    #[test]
+    fn partial_blocks() {
+        let mut rng = Random::from_seed(1);
+        let mut throwaway_rng = Random::from_seed(2);
+        let map_size = Point::new(BLOCK_SIZE * 2 + 5, BLOCK_SIZE + 2);
+        let map = generate_map(&mut rng, &mut throwaway_rng, map_size, Point::new(0, 0));
+        assert_eq!(map.len(), (map_size.x * map_size.y) as usize);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{
+    item,
+    monster::{self, CompanionBonus},
+    player::Mind,
+    point::Point,
+    report::RunStats,
+    save,
+    state::{Challenge, GameSession, ReplayHeader, ReplayStream, Side, State},
+    window::Window,
+};
+
+use std::{collections::BTreeMap, error::Error, fs::File, io::Read, path::Path};
+
+use serde::Serialize;
+
+/// What's inside a save or a replay file.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Inspection {
+    Save(Box<SaveInspection>),
+    Replay(Box<ReplayInspection>),
+}
+
+#[derive(Serialize)]
+pub struct SaveInspection {
+    pub header: save::Header,
+    pub turn: i32,
+    pub tick_id: i32,
+    pub challenge: Challenge,
+    pub game_session: GameSession,
+    pub side: Side,
+    pub player: PlayerStats,
+    /// The positions of the chunks that were generated so far.
+    pub chunks: Vec<Point>,
+    /// The living monsters in all the chunks.
+    pub monsters: BTreeMap<monster::Kind, usize>,
+    /// From the bottom to the top.
+    pub window_stack: Vec<Window>,
+}
+
+#[derive(Serialize)]
+pub struct PlayerStats {
+    pub pos: Point,
+    pub alive: bool,
+    pub invincible: bool,
+    pub mind: Mind,
+    pub will: i32,
+    pub tolerance: i32,
+    pub panic: i32,
+    pub stun: i32,
+    pub ap: i32,
+    pub inventory: BTreeMap<item::Kind, usize>,
+    pub bonuses: Vec<CompanionBonus>,
+    pub current_high_streak: i32,
+    pub longest_high_streak: i32,
+    pub run_stats: RunStats,
+}
+
+#[derive(Serialize)]
+pub struct ReplayInspection {
+    pub gzipped: bool,
+    pub header: ReplayHeader,
+    /// The number of inputs or commands (see `header.stream`).
+    pub entries: usize,
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Read the save or replay at `path`. Nothing is played or shown.
+pub fn inspect(path: &Path) -> Result<Inspection, Box<dyn Error>> {
+    let mut start = vec![];
+    File::open(path)?
+        .take(save::MAGIC.len() as u64)
+        .read_to_end(&mut start)?;
+
+    // NOTE: a replay is text (possibly gzipped) that starts with
+    // either the JSON header or the seed of a legacy replay. A save
+    // is binary and the legacy ones have no magic.
+    let gzipped = start.starts_with(&GZIP_MAGIC);
+    let text = start
+        .first()
+        .is_some_and(|&byte| byte == b'{' || byte.is_ascii_digit());
+    if start.starts_with(save::MAGIC) || !(gzipped || text) {
+        let (header, state) = save::read(path)?;
+        Ok(Inspection::Save(Box::new(inspect_save(header, &state))))
+    } else {
+        Ok(Inspection::Replay(Box::new(inspect_replay(path, gzipped)?)))
+    }
+}
+
+fn inspect_save(header: save::Header, state: &State) -> SaveInspection {
+    let player = &state.player;
+    let mut inventory = BTreeMap::new();
+    for item in &player.inventory {
+        *inventory.entry(item.kind).or_insert(0) += 1;
+    }
+
+    let chunks = state.world.positions_of_all_chunks();
+    let mut monsters = BTreeMap::new();
+    for &chunk_pos in &chunks {
+        if let Some(chunk) = state.world.chunk(chunk_pos) {
+            for monster in chunk.monsters() {
+                *monsters.entry(monster.kind).or_insert(0) += 1;
+            }
+        }
+    }
+
+    SaveInspection {
+        header,
+        turn: state.turn,
+        tick_id: state.tick_id,
+        challenge: state.challenge,
+        game_session: state.game_session,
+        side: state.side,
+        player: PlayerStats {
+            pos: player.pos,
+            alive: player.alive(),
+            invincible: player.invincible,
+            mind: player.mind,
+            will: player.will.to_int(),
+            tolerance: player.tolerance,
+            panic: player.panic.to_int(),
+            stun: player.stun.to_int(),
+            ap: player.ap(),
+            inventory,
+            bonuses: player.bonuses.clone(),
+            current_high_streak: player.current_high_streak,
+            longest_high_streak: player.longest_high_streak,
+            run_stats: player.run_stats.clone(),
+        },
+        chunks,
+        monsters,
+        window_stack: state.window_stack.windows().cloned().collect(),
+    }
+}
+
+fn inspect_replay(path: &Path, gzipped: bool) -> Result<ReplayInspection, Box<dyn Error>> {
+    if !cfg!(feature = "replay") {
+        throw!("This is a replay, but the game was built without the `replay` feature.");
+    }
+    let state = State::load_replay_for_tools(path)?;
+    let header = match state.replay_header.clone() {
+        Some(header) => header,
+        None => throw!("The replay has no header."),
+    };
+    let entries = match header.stream {
+        ReplayStream::Inputs => state.inputs.len(),
+        ReplayStream::Commands => state.recorded_commands.len(),
+    };
+    Ok(ReplayInspection {
+        gzipped,
+        header,
+        entries,
+    })
+}
@@ -92,6 +92,7 @@ pub enum KeyCode {
     Esc,
     QuestionMark,
     Menu,
+    Backspace,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -4,22 +4,28 @@ macro_rules! throw {
     };
 }
 
+pub mod agent_protocol;
 pub mod ai;
 pub mod animation;
 pub mod audio;
+pub mod autoplay;
+pub mod balance;
 pub mod blocker;
 pub mod color;
 pub mod engine;
 pub mod error;
 pub mod formula;
+pub mod fuzz;
 pub mod game;
 pub mod gamepad;
 pub mod generators;
 pub mod graphic;
 pub mod graphics;
+pub mod inspect;
 pub mod item;
 pub mod keys;
 pub mod level;
+pub mod map_export;
 pub mod metadata;
 pub mod monster;
 pub mod palette;
@@ -30,7 +36,13 @@ pub mod random;
 pub mod ranged_int;
 pub mod rect;
 pub mod render;
+pub mod replay_diff;
+pub mod replay_minimise;
+pub mod report;
+pub mod save;
+pub mod session;
 pub mod settings;
+pub mod share_code;
 pub mod state;
 pub mod stats;
 pub mod timer;
@@ -91,9 +103,59 @@ fn run_glutin(
     log::error!("The \"glutin-backend\" feature was not compiled in.");
 }
 
+#[allow(unused_variables, dead_code, clippy::needless_pass_by_value)]
+fn run_terminal(
+    window_title: &str,
+    settings_store: settings::FileSystemStore,
+    state: state::State,
+) {
+    log::info!("Using the terminal backend");
+
+    #[cfg(all(feature = "terminal-backend", unix))]
+    let result = engine::terminal::main_loop(window_title, settings_store, Box::new(state));
+    #[cfg(all(feature = "terminal-backend", unix))]
+    if let Err(err) = result {
+        log::error!("Error occured in the terminal main_loop: {}", err);
+    };
+
+    #[cfg(not(all(feature = "terminal-backend", unix)))]
+    log::error!(
+        "The \"terminal-backend\" feature was not compiled in or this isn't a Unix system."
+    );
+}
+
+/// Parse a size in pixels such as `1280x720`.
+fn parse_size(size: &str) -> Result<point::Point, Box<dyn std::error::Error>> {
+    let mut parts = size.split('x');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(width), Some(height), None) => {
+            let size = point::Point::new(width.trim().parse()?, height.trim().parse()?);
+            if size.x <= 0 || size.y <= 0 {
+                throw!(&format!("The size must be positive, got: `{}`", size));
+            }
+            Ok(size)
+        }
+        _ => throw!(&format!(
+            "Expected a size in the `WIDTHxHEIGHT` format, got: `{}`",
+            size
+        )),
+    }
+}
+
+fn parse_point(point: &str) -> Result<point::Point, Box<dyn std::error::Error>> {
+    let mut parts = point.split(',');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(x), Some(y), None) => Ok(point::Point::new(x.trim().parse()?, y.trim().parse()?)),
+        _ => throw!(&format!(
+            "Expected a position in the `X,Y` format, got: `{}`",
+            point
+        )),
+    }
+}
+
 pub fn run() -> Result<(), Box<dyn std::error::Error>> {
     use crate::settings::Store;
-    use clap::{App, Arg};
+    use clap::{App, Arg, SubCommand};
     use simplelog::{CombinedLogger, LevelFilter, SharedLogger, SimpleLogger, WriteLogger};
     use std::fs::File;
 
@@ -122,6 +184,166 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
             Arg::with_name("debug")
                 .long("debug")
                 .help("Debug mode. Output detailed messages and replay logs. This can be really verbose and take up massive amounts of space."),
+        )
+        .arg(
+            Arg::with_name("agent-protocol")
+                .long("agent-protocol")
+                .help(
+                    "Play a new game without a window. Read the player's \
+                     commands from stdin and write what they see to stdout, \
+                     both as JSON, one per line. Implies `--quiet`.",
+                ),
+        )
+        .arg(
+            Arg::with_name("backend")
+                .long("backend")
+                .value_name("NAME")
+                .takes_value(true)
+                .possible_values(&engine::AVAILABLE_BACKENDS)
+                .help(
+                    "Use this graphics backend instead of the one in the settings. \
+                     `terminal` plays the game in the terminal it was started from.",
+                ),
+        )
+        .arg(
+            Arg::with_name("autoplay")
+                .long("autoplay")
+                .help(
+                    "Let the built-in bot play a new game. With `--headless`, \
+                     play it to the end without a window.",
+                ),
+        )
+        .arg(
+            Arg::with_name("landscape")
+                .help("The kind of world a new game takes place in. Forest by default.")
+                .long("landscape")
+                .takes_value(true)
+                .possible_values(generators::LANDSCAPE_NAMES),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .help(
+                    "Start a new game with this seed. Either a number or a \
+                     share code which sets the challenge and landscape too.",
+                )
+                .long("seed")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("daily")
+                .help("Play the Daily Run: the same game for everyone on today's date")
+                .long("daily")
+                .conflicts_with("seed"),
+        )
+        .subcommand(
+            SubCommand::with_name("balance")
+                .about(
+                    "Play a game for each of many seeds without a window and \
+                     print a JSON summary: the victory rate, how long the player \
+                     survived, what killed them and how many doses they used. \
+                     Pass `--quiet` to keep the log messages out of the summary.",
+                )
+                .arg(
+                    Arg::with_name("games")
+                        .help("How many games (seeds) to play")
+                        .long("games")
+                        .value_name("COUNT")
+                        .takes_value(true)
+                        .default_value("1000"),
+                )
+                .arg(
+                    Arg::with_name("first-seed")
+                        .help("The seed of the first game. The others follow it.")
+                        .long("first-seed")
+                        .value_name("SEED")
+                        .takes_value(true)
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::with_name("policy")
+                        .help("Who plays the games")
+                        .long("policy")
+                        .takes_value(true)
+                        .possible_values(balance::POLICY_NAMES)
+                        .default_value("autoplay"),
+                )
+                .arg(
+                    Arg::with_name("csv")
+                        .help("Write the outcome of every game to this CSV file")
+                        .long("csv")
+                        .value_name("FILE")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("inspect")
+                .about(
+                    "Print what's in a save or a replay file as JSON without \
+                     opening a window: the header and, for a save, the player, \
+                     the generated chunks, the monsters and the open windows. \
+                     Pass `--quiet` to keep the log messages out of the output.",
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("map")
+                .about(
+                    "Write the map of the world around a point into \
+                     `<OUTPUT>.txt` and `<OUTPUT>.png`. The world is either \
+                     generated from a seed or taken from a save file.",
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .value_name("OUTPUT")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("save")
+                        .help("Take the world from this save file")
+                        .long("save")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .conflicts_with("seed"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .help(
+                            "Generate the world from this seed (a number or a \
+                             share code). A random one by default.",
+                        )
+                        .long("seed")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("center")
+                        .help("The centre of the map. The player's position by default.")
+                        .long("center")
+                        .value_name("X,Y")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("radius")
+                        .help("How many tiles the map goes in each direction from the centre")
+                        .long("radius")
+                        .takes_value(true)
+                        .default_value("64"),
+                )
+                .arg(
+                    Arg::with_name("tile-size")
+                        .help("The width and height of a tile in the PNG in pixels")
+                        .long("tile-size")
+                        .takes_value(true)
+                        .default_value("4"),
+                )
+                .arg(
+                    Arg::with_name("explored-only")
+                        .help("Leave out the cells the player hasn't seen")
+                        .long("explored-only"),
+                ),
         );
 
     if cfg!(feature = "cheating") {
@@ -161,16 +383,134 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
                     )
                     .long("replay-full-speed"),
             )
+            .arg(
+                Arg::with_name("replay-seek")
+                    .help(
+                        "Fast-forward the replay to this tick and pause it \
+                         there",
+                    )
+                    .long("replay-seek")
+                    .value_name("TICK")
+                    .takes_value(true)
+                    .conflicts_with("headless"),
+            )
 	    .arg(
 		Arg::with_name("headless")
-		    .help("Run the replay in a headless mode. No window will be open but the game will play through the full replay log. This can be useful for automated testing.")
+		    .help("Run the replay (or the `autoplay` game) in a headless mode. No window will be open but the game will play through the full replay log. This can be useful for automated testing.")
 		    .long("headless"))
+            .arg(
+                Arg::with_name("export-frames")
+                    .help(
+                        "Render every frame of the headless replay into a numbered \
+                         PNG image in this directory",
+                    )
+                    .long("export-frames")
+                    .value_name("DIR")
+                    .takes_value(true)
+                    .requires_all(&["headless", "replay"]),
+            )
+            .arg(
+                Arg::with_name("export-frames-size")
+                    .help(
+                        "Size of the exported frames, e.g. `1920x1080`. The game \
+                         is scaled to fit and letterboxed. Defaults to the window \
+                         size from the replay.",
+                    )
+                    .long("export-frames-size")
+                    .value_name("WIDTHxHEIGHT")
+                    .takes_value(true)
+                    .requires("export-frames"),
+            )
+            .arg(
+                Arg::with_name("report")
+                    .help(
+                        "Write a JSON summary of the game (turns, items used, \
+                         monsters killed, cause of death, etc.) to this file \
+                         once the headless replay or game finishes",
+                    )
+                    .long("report")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .requires("headless"),
+            )
             .arg(
                 Arg::with_name("replay-file")
                     .help("Path where to store the replay log.")
                     .long("replay-file")
                     .value_name("FILE")
                     .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("record-commands")
+                    .help(
+                        "Record the commands the player carries out instead of \
+                         the keys and mouse. When playing a replay back, write its \
+                         commands into a new replay.",
+                    )
+                    .long("record-commands"),
+            )
+            .arg(
+                Arg::with_name("fuzz")
+                    .help(
+                        "Play this many games with random seeds, challenges and \
+                         keys without a window. Save the replays of the ones that \
+                         crash into the `fuzz-crashes` directory.",
+                    )
+                    .long("fuzz")
+                    .value_name("ITERATIONS")
+                    .takes_value(true),
+            )
+            .subcommand(
+                SubCommand::with_name("replay-diff")
+                    .about(
+                        "Play the replays back without a window and print a JSON \
+                         report of the first tick where they diverge. With a single \
+                         replay, compare it against its own recorded verifications. \
+                         Pass `--quiet` to keep the log messages out of the report.",
+                    )
+                    .arg(
+                        Arg::with_name("first")
+                            .value_name("FILE")
+                            .required(true),
+                    )
+                    .arg(Arg::with_name("second").value_name("FILE")),
+            )
+            .subcommand(
+                SubCommand::with_name("replay-minimise")
+                    .about(
+                        "Shorten a failing replay by repeatedly removing its \
+                         inputs while it keeps failing the same way. Every attempt \
+                         plays the replay back without a window.",
+                    )
+                    .arg(
+                        Arg::with_name("replay")
+                            .value_name("FILE")
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("failure")
+                            .help("The failure the shortened replay must reproduce")
+                            .long("failure")
+                            .takes_value(true)
+                            .possible_values(replay_minimise::FAILURE_NAMES)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("output")
+                            .help("Path where to store the shortened replay")
+                            .long("output")
+                            .value_name("FILE")
+                            .takes_value(true)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("max-runs")
+                            .help("Stop after playing the replay this many times")
+                            .long("max-runs")
+                            .value_name("COUNT")
+                            .takes_value(true)
+                            .default_value("500"),
+                    ),
             );
     }
 
@@ -192,7 +532,14 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         LevelFilter::Info
     };
 
-    if !matches.is_present("quiet") {
+    // NOTE: the agent protocol uses stdout so nothing else can write
+    // there. Neither can the log when the game's drawn in the terminal.
+    let terminal_backend = || match matches.value_of("backend") {
+        Some(backend) => backend == "terminal",
+        None => settings::FileSystemStore::new().load().backend == "terminal",
+    };
+    if !matches.is_present("quiet") && !matches.is_present("agent-protocol") && !terminal_backend()
+    {
         loggers.push(SimpleLogger::new(log_level, Config::default()) as Box<dyn SharedLogger>);
     }
 
@@ -243,17 +590,205 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         crate::engine::AVAILABLE_BACKENDS
     );
 
+    if let Some(matches) = matches.subcommand_matches("replay-diff") {
+        let first = matches.value_of("first").map(std::path::Path::new);
+        let second = matches.value_of("second").map(std::path::Path::new);
+        if let Some(first) = first {
+            let report = replay_diff::diff_replays(first, second)?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("inspect") {
+        if let Some(file) = matches.value_of("file") {
+            let inspection = inspect::inspect(std::path::Path::new(file))?;
+            println!("{}", serde_json::to_string_pretty(&inspection)?);
+        }
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("map") {
+        let mut state = if let Some(save) = matches.value_of("save") {
+            save::read(std::path::Path::new(save))?.1
+        } else {
+            let game = match matches.value_of("seed") {
+                Some(seed) => share_code::ShareCode::from_seed_text(
+                    seed,
+                    state::Challenge::default(),
+                    generators::Landscape::default(),
+                )?,
+                None => share_code::ShareCode {
+                    seed: util::random_seed(),
+                    ..Default::default()
+                },
+            };
+            log::info!("Generating the map for: {}", game);
+            state::State::new_session(game.seed, game.challenge, game.landscape)
+        };
+        let center = match matches.value_of("center") {
+            Some(center) => parse_point(center)?,
+            None => state.player.pos,
+        };
+        let radius: i32 = matches.value_of("radius").unwrap_or("64").parse()?;
+        if radius < 0 {
+            throw!("The radius must not be negative.");
+        }
+        let area = rect::Rectangle::center(center, point::Point::from_i32(radius));
+        let palette = state.palette;
+        let map = map_export::Map::new(
+            &mut state.world,
+            Some(&state.player),
+            &palette,
+            area,
+            matches.is_present("explored-only"),
+        );
+
+        let output = matches.value_of("output").unwrap_or("map");
+        let text_path = format!("{}.txt", output);
+        std::fs::write(&text_path, map.to_text())?;
+        log::info!("Wrote the map to: {}", text_path);
+
+        #[cfg(feature = "glutin-backend")]
+        {
+            let tile_size: u32 = matches.value_of("tile-size").unwrap_or("4").parse()?;
+            if tile_size == 0 {
+                throw!("The tile size must be at least one pixel.");
+            }
+            let png_path = format!("{}.png", output);
+            map.to_image(tile_size).save(&png_path)?;
+            log::info!("Wrote the map to: {}", png_path);
+        }
+        #[cfg(not(feature = "glutin-backend"))]
+        log::warn!("The game was built without the `glutin-backend` feature. Not writing the PNG.");
+
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("balance") {
+        if let (Some(games), Some(first_seed), Some(policy)) = (
+            matches.value_of("games"),
+            matches.value_of("first-seed"),
+            matches.value_of("policy"),
+        ) {
+            let policy = policy.parse()?;
+            let first_seed = first_seed.parse()?;
+            // NOTE: use the default challenge rather than the local
+            // settings so the numbers are the same everywhere.
+            let challenge = state::Challenge::default();
+            let games = balance::run(policy, challenge, first_seed, games.parse()?);
+            if let Some(csv_path) = matches.value_of("csv") {
+                balance::write_csv(std::io::BufWriter::new(File::create(csv_path)?), &games)?;
+                log::info!("Wrote the games to: {}", csv_path);
+            }
+            let summary = balance::Summary::new(policy, challenge, first_seed, &games);
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        }
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("replay-minimise") {
+        if let (Some(replay), Some(failure), Some(output), Some(max_runs)) = (
+            matches.value_of("replay"),
+            matches.value_of("failure"),
+            matches.value_of("output"),
+            matches.value_of("max-runs"),
+        ) {
+            let summary = replay_minimise::minimise(
+                std::path::Path::new(replay),
+                failure.parse()?,
+                std::path::Path::new(output),
+                max_runs.parse()?,
+            )?;
+            log::info!(
+                "Minimised the replay from {} to {} inputs in {} runs. Saved to: {}",
+                summary.original_inputs,
+                summary.minimised_inputs,
+                summary.runs,
+                output
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(iterations) = matches.value_of("fuzz") {
+        let summary = fuzz::run(iterations.parse()?)?;
+        if !summary.crashes.is_empty() {
+            throw!(&format!(
+                "{} of {} games crashed. Their replays are in: {}",
+                summary.crashes.len(),
+                summary.games,
+                fuzz::CRASH_DIR
+            ));
+        }
+        log::info!("All {} games finished without crashing.", summary.games);
+        return Ok(());
+    }
+
     let mut settings_store = settings::FileSystemStore::new();
     let mut settings = settings_store.load();
-    let backend = settings.backend.clone();
+    let backend = matches
+        .value_of("backend")
+        .map_or_else(|| settings.backend.clone(), String::from);
     let challenge = settings.challenge();
     let palette = settings.palette();
+    let landscape = match matches.value_of("landscape") {
+        Some(landscape) => landscape.parse()?,
+        None => generators::Landscape::default(),
+    };
+    let game = if matches.is_present("daily") {
+        share_code::ShareCode::daily(chrono::Local::now().date_naive())
+    } else if let Some(seed) = matches.value_of("seed") {
+        share_code::ShareCode::from_seed_text(seed, challenge, landscape)?
+    } else {
+        share_code::ShareCode {
+            seed: util::random_seed(),
+            challenge,
+            landscape,
+        }
+    };
+
+    if matches.is_present("agent-protocol") {
+        log::info!("Playing over the agent protocol with: {}", game);
+        let session =
+            session::GameSession::with_landscape(game.seed, game.challenge, game.landscape);
+        let stdin = std::io::stdin();
+        return agent_protocol::run(session, stdin.lock(), std::io::stdout());
+    }
+
+    if matches.is_present("autoplay") && matches.is_present("replay") {
+        throw!("The `autoplay` option can't be used with a replay.");
+    }
+
+    if matches.is_present("autoplay") && matches.is_present("headless") {
+        log::info!("Autoplaying a game without a window with: {}", game);
+        let mut session =
+            session::GameSession::with_landscape(game.seed, game.challenge, game.landscape);
+        session.play(autoplay::next_command)?;
+        log::info!(
+            "The game ended with {:?} after {} turns.",
+            session.outcome(),
+            session.state().turn
+        );
+
+        if let Some(report_path) = matches.value_of("report") {
+            let report = report::Report::new(session.state());
+            let report_file = File::create(report_path)?;
+            serde_json::to_writer_pretty(report_file, &report)?;
+            log::info!("Wrote the game report to: {}", report_path);
+        }
+
+        return Ok(());
+    }
 
     let state = if let Some(replay) = matches.value_of("replay") {
-        if matches.is_present("replay-file") {
+        if matches.is_present("seed") || matches.is_present("daily") {
+            throw!("The `seed` and `daily` options can't be used with a replay.");
+        }
+        if matches.is_present("replay-file") && !matches.is_present("record-commands") {
             throw!(
                 "The `replay-file` option can only be used during regular \
-                 game, not replay."
+                 game or with `record-commands`, not replay."
             );
         }
 
@@ -268,19 +803,33 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         };
 
         let replay_path = std::path::Path::new(replay);
-        state::State::replay_game(
-            WORLD_SIZE,
-            point::Point::from_i32(DISPLAYED_MAP_SIZE),
-            PANEL_WIDTH,
+        // NOTE: the challenge, palette and display all come from the
+        // replay header, not the local settings.
+        let mut state = state::State::replay_game(
             replay_path,
             matches.is_present("cheating"),
             matches.is_present("invincible"),
             matches.is_present("replay-full-speed"),
             exit_after,
             matches.is_present("debug"),
-            challenge,
-            palette,
-        )?
+        )?;
+
+        if matches.is_present("record-commands") {
+            let commands_path = match matches.value_of("replay-file") {
+                Some(file) => Some(file.into()),
+                None => state::generate_replay_path(),
+            };
+            if let Some(commands_path) = commands_path {
+                state.record_commands(&commands_path)?;
+            }
+        }
+
+        if let Some(tick) = matches.value_of("replay-seek") {
+            state.paused = true;
+            state.seek_replay(tick.parse()?)?;
+        }
+
+        state
     } else {
         if matches.is_present("replay-full-speed") {
             throw!(
@@ -304,14 +853,40 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
             PANEL_WIDTH,
             matches.is_present("exit-after"),
             matches.is_present("debug"),
+            matches.is_present("invincible"),
             replay_file,
-            challenge,
+            // NOTE: the bot doesn't press any keys so its games can
+            // only be replayed from the commands.
+            if matches.is_present("record-commands") || matches.is_present("autoplay") {
+                state::ReplayStream::Commands
+            } else {
+                state::ReplayStream::Inputs
+            },
+            game.seed,
+            game.challenge,
+            game.landscape,
             palette,
+            state::DisplaySettings::from_settings(&settings),
         );
-        state.player.invincible = matches.is_present("invincible");
+        state.autoplay = matches.is_present("autoplay");
+
+        if !state.autoplay {
+            let slots = save::Slots::in_data_dir();
+            match slots.begin_session() {
+                Ok(true) => state.recoverable_session = slots.latest_autosave(),
+                Ok(false) => {}
+                Err(error) => log::error!("Could not mark the session as running: {:?}", error),
+            }
+        }
 
         state.window_stack = windows::Windows::new(window::Window::Game);
-        if settings.first_ever_startup {
+        // NOTE: the player asked for a specific game so start it
+        // right away.
+        let game_requested = matches.is_present("seed") || matches.is_present("daily");
+        if state.autoplay || game_requested {
+            state.generate_world();
+            state.game_session = state::GameSession::InProgress;
+        } else if settings.first_ever_startup && state.recoverable_session.is_none() {
             // Start the game with the game on top, don't push in any other window.
             // Just like in Braid, basically.
             state.generate_world();
@@ -328,23 +903,46 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         state
     };
 
-    let background = palette.unexplored_background;
+    let background = state.palette.unexplored_background;
     let game_title = metadata::TITLE;
 
     if matches.is_present("headless") && matches.is_present("replay") {
         log::info!("Run in the headless mode");
 
-        let result = engine::headless::main_loop(settings_store, Box::new(state));
+        let frame_export = match matches.value_of("export-frames") {
+            Some(dir) => {
+                let size_px = match matches.value_of("export-frames-size") {
+                    Some(size) => Some(parse_size(size)?),
+                    None => None,
+                };
+                Some(engine::headless::FrameExport {
+                    dir: dir.into(),
+                    size_px,
+                })
+            }
+            None => None,
+        };
+
+        let final_state = engine::headless::run(settings_store, Box::new(state), frame_export)?;
+
+        if let Some(report_path) = matches.value_of("report") {
+            let report = report::Report::new(&final_state);
+            let report_file = File::create(report_path)?;
+            serde_json::to_writer_pretty(report_file, &report)?;
+            log::info!("Wrote the game report to: {}", report_path);
+        }
 
-        return result;
+        return Ok(());
     }
 
     match backend.as_str() {
         "glutin" => run_glutin(background, game_title, settings_store, state),
+        "terminal" => run_terminal(game_title, settings_store, state),
         _ => {
             log::error!("Unknown backend: {}", backend);
         }
     }
+    save::Slots::in_data_dir().end_session();
 
     Ok(())
 }
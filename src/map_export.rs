@@ -0,0 +1,169 @@
+use crate::{
+    color::Color, level::TileKind, palette::Palette, player::Player, point::Point, rect::Rectangle,
+    world::World,
+};
+
+/// What's shown in a cell of the exported map.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Layer {
+    Unexplored,
+    Ground,
+    Tree,
+    Item,
+    Monster,
+    Player,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MapCell {
+    pub layer: Layer,
+    pub glyph: char,
+    pub color: Color,
+}
+
+/// A part of the world flattened into a single glyph and colour per
+/// cell. It can be written out as text or (with the `glutin-backend`
+/// feature) as a PNG.
+pub struct Map {
+    pub area: Rectangle,
+    pub cells: Vec<MapCell>,
+    explored_background: Color,
+    unexplored_background: Color,
+}
+
+impl Map {
+    /// Generate any missing chunks in `area` and take the tiles,
+    /// items, monsters and the `player` from them. With
+    /// `explored_only`, the cells the player hasn't seen are left
+    /// empty.
+    pub fn new(
+        world: &mut World,
+        player: Option<&Player>,
+        palette: &Palette,
+        area: Rectangle,
+        explored_only: bool,
+    ) -> Self {
+        for pos in area.points() {
+            if world.within_bounds(pos) && world.chunk(pos).is_none() {
+                world.ensure_chunk_at_pos(pos);
+            }
+        }
+
+        let unexplored = MapCell {
+            layer: Layer::Unexplored,
+            glyph: ' ',
+            color: palette.unexplored_background,
+        };
+        let width = area.width() as usize;
+        let mut cells = vec![unexplored; width * area.height() as usize];
+        let index = |pos: Point| {
+            let offset = pos - area.top_left();
+            offset.y as usize * width + offset.x as usize
+        };
+
+        for chunk in world.chunks(area) {
+            for (pos, cell) in chunk.cells() {
+                if !area.contains(pos) || (explored_only && !cell.explored) {
+                    continue;
+                }
+                let map_cell = if let Some(item) = cell.items.last() {
+                    MapCell {
+                        layer: Layer::Item,
+                        glyph: item.graphic().into(),
+                        color: item.color(palette),
+                    }
+                } else {
+                    let layer = match cell.tile.kind {
+                        TileKind::Empty => Layer::Ground,
+                        TileKind::Tree => Layer::Tree,
+                    };
+                    MapCell {
+                        layer,
+                        glyph: cell.tile.graphic.into(),
+                        color: cell.tile.color(palette),
+                    }
+                };
+                cells[index(pos)] = map_cell;
+            }
+        }
+
+        for monster in world.monsters(area) {
+            let explored = world
+                .cell(monster.position)
+                .is_some_and(|cell| cell.explored);
+            if explored_only && !explored {
+                continue;
+            }
+            cells[index(monster.position)] = MapCell {
+                layer: Layer::Monster,
+                glyph: monster.graphic().into(),
+                color: monster.color(palette),
+            };
+        }
+
+        if let Some(player) = player {
+            if area.contains(player.pos) {
+                cells[index(player.pos)] = MapCell {
+                    layer: Layer::Player,
+                    glyph: player.graphic().into(),
+                    color: player.color(palette),
+                };
+            }
+        }
+
+        Self {
+            area,
+            cells,
+            explored_background: palette.explored_background,
+            unexplored_background: palette.unexplored_background,
+        }
+    }
+
+    pub fn cell(&self, pos: Point) -> Option<&MapCell> {
+        if self.area.contains(pos) {
+            let offset = pos - self.area.top_left();
+            self.cells
+                .get(offset.y as usize * self.area.width() as usize + offset.x as usize)
+        } else {
+            None
+        }
+    }
+
+    /// One line per row of the map, one character per cell.
+    pub fn to_text(&self) -> String {
+        let mut result = String::with_capacity(self.cells.len() + self.area.height() as usize);
+        for row in self.cells.chunks(self.area.width() as usize) {
+            let line: String = row.iter().map(|cell| cell.glyph).collect();
+            result.push_str(line.trim_end());
+            result.push('\n');
+        }
+        result
+    }
+
+    /// `tile_size` pixels for every cell. The trees fill the whole
+    /// cell, the items, monsters and the player a square in the
+    /// middle of it.
+    #[cfg(feature = "glutin-backend")]
+    pub fn to_image(&self, tile_size: u32) -> image::RgbImage {
+        let rgb = |color: Color| image::Rgb([color.r, color.g, color.b]);
+        let width = self.area.width() as u32;
+        let margin = tile_size / 4;
+        image::RgbImage::from_fn(
+            width * tile_size,
+            self.area.height() as u32 * tile_size,
+            |x, y| {
+                let cell = self.cells[((y / tile_size) * width + x / tile_size) as usize];
+                let (x, y) = (x % tile_size, y % tile_size);
+                let inside = (margin..tile_size - margin).contains(&x)
+                    && (margin..tile_size - margin).contains(&y);
+                match cell.layer {
+                    Layer::Unexplored => rgb(self.unexplored_background),
+                    Layer::Ground => rgb(self.explored_background),
+                    Layer::Tree => rgb(cell.color),
+                    Layer::Item | Layer::Monster | Layer::Player if inside => rgb(cell.color),
+                    Layer::Item | Layer::Monster | Layer::Player => rgb(self.explored_background),
+                }
+            },
+        )
+    }
+}
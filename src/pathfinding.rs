@@ -272,7 +272,16 @@ mod test {
             will: 3,
         };
         let challenge = Default::default();
-        let mut world = World::new(seed, 64, 32, player_info, challenge);
+        let regions = false;
+        let mut world = World::new(
+            seed,
+            64,
+            32,
+            player_info,
+            challenge,
+            Default::default(),
+            regions,
+        );
         // clear out the world
         for x in 0..16 {
             for y in 0..16 {
@@ -8,6 +8,8 @@ use crate::{
     palette::Palette,
     point::Point,
     ranged_int::Ranged,
+    report::RunStats,
+    save,
 };
 
 use std::fmt::{Display, Error, Formatter};
@@ -82,7 +84,7 @@ impl Default for Bonus {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub enum CauseOfDeath {
     Exhausted,
     Overdosed,
@@ -123,6 +125,8 @@ pub struct Player {
     /// How many times has the player been reset.
     pub reset_count: i32,
 
+    pub run_stats: RunStats,
+
     pub dead: bool,
     pub invincible: bool,
     pub perpetrator: Option<Monster>,
@@ -144,6 +148,7 @@ impl Player {
         player.current_high_streak = 0;
         player.longest_high_streak = 0;
         player.reset_count = 0;
+        player.run_stats = RunStats::default();
 
         player
     }
@@ -277,3 +282,32 @@ impl Player {
         }
     }
 }
+
+/// The run stats weren't kept before, they start from the load.
+impl From<save::v1::Player> for Player {
+    fn from(player: save::v1::Player) -> Self {
+        Player {
+            mind: player.mind,
+            will: player.will,
+            tolerance: player.tolerance,
+            panic: player.panic,
+            stun: player.stun,
+            pos: player.pos,
+            motion_animation: player.motion_animation,
+            color_index: player.color_index,
+            graphic: player.graphic,
+            inventory: player.inventory,
+            anxiety_counter: player.anxiety_counter,
+            bonus: player.bonus,
+            bonuses: player.bonuses,
+            current_high_streak: player.current_high_streak,
+            longest_high_streak: player.longest_high_streak,
+            reset_count: player.reset_count,
+            run_stats: RunStats::default(),
+            dead: player.dead,
+            invincible: player.invincible,
+            perpetrator: player.perpetrator,
+            ap: player.ap,
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{
+    engine::headless,
+    error::Desync,
+    settings::NoOpStore,
+    state::{ReplayTick, State, VerificationDiff, VerificationWrapper},
+};
+
+use std::{error::Error, path::Path};
+
+use serde::Serialize;
+
+/// The result of comparing one or two replays.
+///
+/// NOTE: the state at tick N is taken before the inputs of that tick
+/// are processed. So if the replays diverge at tick N, whatever
+/// caused it happened during tick N - 1.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub replays: Vec<ReplaySummary>,
+
+    /// The first tick where the verifications recorded in the two
+    /// replay files differ. This is what we get when the same game
+    /// was recorded by two different builds.
+    pub first_recorded_divergence: Option<i32>,
+
+    /// The first tick where the two replays played back by this
+    /// build differ. The "expected" values come from the first
+    /// replay and the "actual" ones from the second.
+    pub first_divergence: Option<Divergence>,
+}
+
+/// A tick where the two replays differ.
+#[derive(Debug, Serialize)]
+pub struct Divergence {
+    pub tick_id: i32,
+
+    /// What's different about the game. This is `None` when one of
+    /// the replays ended before this tick.
+    pub diff: Option<VerificationDiff>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplaySummary {
+    pub path: String,
+    pub ticks: usize,
+
+    /// The first tick where this build doesn't match what the replay
+    /// file recorded.
+    pub desync: Option<Desync>,
+}
+
+/// Play the replays back and compare them tick by tick. With only one
+/// replay, it's compared against its own recorded verifications.
+pub fn diff_replays(first: &Path, second: Option<&Path>) -> Result<Report, Box<dyn Error>> {
+    let first_state = State::load_replay_for_tools(first)?;
+    let second_state = match second {
+        Some(second) => Some(State::load_replay_for_tools(second)?),
+        None => None,
+    };
+    if let Some(second_state) = &second_state {
+        let streams = (first_state.replay_stream(), second_state.replay_stream());
+        if streams.0 != streams.1 {
+            throw!(&format!(
+                "Can't compare a replay of {:?} with a replay of {:?}.",
+                streams.0, streams.1
+            ));
+        }
+    }
+
+    let first_ticks = play(first_state)?;
+    let mut replays = vec![summary(first, &first_ticks)];
+
+    let mut first_recorded_divergence = None;
+    let mut first_divergence = None;
+
+    if let (Some(second), Some(second_state)) = (second, second_state) {
+        let second_ticks = play(second_state)?;
+        replays.push(summary(second, &second_ticks));
+
+        first_recorded_divergence = find_divergence(&first_ticks, &second_ticks, |a, b| {
+            match (recorded_hash(a), recorded_hash(b)) {
+                (Some(a), Some(b)) if a != b => Some(()),
+                _ => None,
+            }
+        })
+        .map(|divergence| divergence.0);
+
+        first_divergence =
+            find_divergence(&first_ticks, &second_ticks, |a, b| a.actual.diff(&b.actual))
+                .map(|(tick_id, diff)| Divergence { tick_id, diff });
+    }
+
+    Ok(Report {
+        replays,
+        first_recorded_divergence,
+        first_divergence,
+    })
+}
+
+/// Find the first tick where `diff` returns something. If the replays
+/// match for as long as they both go, the first tick that only the
+/// longer one has is the divergence.
+fn find_divergence<T>(
+    first: &[ReplayTick],
+    second: &[ReplayTick],
+    mut diff: impl FnMut(&ReplayTick, &ReplayTick) -> Option<T>,
+) -> Option<(i32, Option<T>)> {
+    let found = first
+        .iter()
+        .zip(second)
+        .find_map(|(a, b)| diff(a, b).map(|diff| (a.actual.tick_id, Some(diff))));
+    found.or_else(|| {
+        let longer = if first.len() > second.len() {
+            first
+        } else {
+            second
+        };
+        longer
+            .get(first.len().min(second.len()))
+            .map(|tick| (tick.actual.tick_id, None))
+    })
+}
+
+/// Play the loaded replay back in the headless mode and collect the
+/// game state at every tick.
+fn play(mut state: State) -> Result<Vec<ReplayTick>, Box<dyn Error>> {
+    state.replay_ticks = Some(vec![]);
+
+    let state = headless::run(NoOpStore, Box::new(state), None)?;
+    Ok(state.replay_ticks.unwrap_or_default())
+}
+
+fn summary(replay_path: &Path, ticks: &[ReplayTick]) -> ReplaySummary {
+    let desync = ticks
+        .iter()
+        .find_map(|tick| tick.recorded.desync(&tick.actual));
+
+    ReplaySummary {
+        path: replay_path.display().to_string(),
+        ticks: ticks.len(),
+        desync,
+    }
+}
+
+fn recorded_hash(tick: &ReplayTick) -> Option<blake3::Hash> {
+    match &tick.recorded {
+        VerificationWrapper::Verification(verification) => Some(verification.hash()),
+        VerificationWrapper::Hash(hash_bytes) => Some(blake3::Hash::from_bytes(*hash_bytes)),
+        VerificationWrapper::None => None,
+    }
+}
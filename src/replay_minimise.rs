@@ -0,0 +1,280 @@
+use crate::{
+    engine::headless,
+    error::ReplayError,
+    formula,
+    player::CauseOfDeath,
+    settings::NoOpStore,
+    state::{
+        self, Input, ReplayHeader, ReplayStream, State, VerificationWrapper, REPLAY_FORMAT_VERSION,
+    },
+};
+
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Once,
+};
+
+/// The failure the minimised replay must still reproduce.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Failure {
+    Panic,
+    Desync,
+    CauseOfDeath(CauseOfDeath),
+}
+
+pub const FAILURE_NAMES: &[&str] = &[
+    "panic",
+    "desync",
+    "exhausted",
+    "overdosed",
+    "lost-will",
+    "killed",
+];
+
+impl FromStr for Failure {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use self::CauseOfDeath::*;
+        let failure = match s {
+            "panic" => Failure::Panic,
+            "desync" => Failure::Desync,
+            "exhausted" => Failure::CauseOfDeath(Exhausted),
+            "overdosed" => Failure::CauseOfDeath(Overdosed),
+            "lost-will" => Failure::CauseOfDeath(LostWill),
+            "killed" => Failure::CauseOfDeath(Killed),
+            _ => throw!(&format!(
+                "Unknown failure: `{}`. Expected one of: {}",
+                s,
+                FAILURE_NAMES.join(", ")
+            )),
+        };
+        Ok(failure)
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Panic => f.write_str("panic"),
+            Failure::Desync => f.write_str("desync"),
+            Failure::CauseOfDeath(cause) => write!(f, "death ({:?})", cause),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub original_inputs: usize,
+    pub minimised_inputs: usize,
+    pub runs: usize,
+}
+
+/// Play the replay back over and over with fewer and fewer inputs
+/// while the failure still happens. Write the shortest replay that
+/// still fails into `output_path`.
+///
+/// First, we look for the shortest prefix of the replay that still
+/// fails. Then we try to remove ever smaller chunks of inputs from
+/// it. Every attempt is a full headless playthrough so this stops
+/// after `max_runs` attempts and writes whatever it's got by then.
+///
+/// NOTE: a desync is only minimised by cutting off the end of the
+/// replay. The recorded verifications don't mean anything once we
+/// start removing inputs so for the other failures they're dropped
+/// altogether.
+///
+/// A panic must be the same one the original replay hit: the same
+/// message at the same place in the code.
+pub fn minimise(
+    replay_path: &Path,
+    failure: Failure,
+    output_path: &Path,
+    max_runs: usize,
+) -> Result<Summary, Box<dyn Error>> {
+    let state = State::load_replay_for_tools(replay_path)?;
+    let mut header = match state.replay_header.clone() {
+        Some(header) => header,
+        None => throw!("The replay doesn't have a header."),
+    };
+    if header.stream != ReplayStream::Inputs {
+        throw!("Only the replays recording the keys and mouse can be minimised.");
+    }
+    // NOTE: we always write the header in the current format.
+    header.replay_format_version = REPLAY_FORMAT_VERSION;
+
+    let mut inputs: Vec<Input> = state.inputs.iter().cloned().collect();
+    let original_inputs = inputs.len();
+    if failure != Failure::Desync {
+        for input in &mut inputs {
+            input.verification = VerificationWrapper::None;
+        }
+    }
+
+    let mut minimiser = Minimiser {
+        header,
+        failure,
+        candidate_path: std::env::temp_dir()
+            .join(format!("dose-response-minimise-{}", std::process::id())),
+        original_panic: None,
+        runs: 0,
+    };
+
+    install_panic_hook();
+    if !minimiser.reproduces(&inputs)? {
+        throw!(&format!(
+            "The replay doesn't fail with: {}. Nothing to minimise.",
+            failure
+        ));
+    }
+
+    // Find the shortest failing prefix. The whole replay fails so
+    // there's always one.
+    let (mut passing, mut failing) = (0, inputs.len());
+    while failing - passing > 1 && minimiser.runs < max_runs {
+        let length = passing + (failing - passing) / 2;
+        if minimiser.reproduces(&inputs[..length])? {
+            failing = length;
+        } else {
+            passing = length;
+        }
+    }
+    inputs.truncate(failing);
+    log::info!("The shortest failing prefix has {} inputs.", inputs.len());
+
+    if failure != Failure::Desync {
+        let mut chunk_size = inputs.len() / 2;
+        while chunk_size > 0 && minimiser.runs < max_runs {
+            let mut start = 0;
+            while start < inputs.len() && minimiser.runs < max_runs {
+                let end = (start + chunk_size).min(inputs.len());
+                let candidate: Vec<Input> = inputs[..start]
+                    .iter()
+                    .chain(&inputs[end..])
+                    .cloned()
+                    .collect();
+                if minimiser.reproduces(&candidate)? {
+                    log::info!(
+                        "Removed inputs {}..{}, {} left.",
+                        start,
+                        end,
+                        candidate.len()
+                    );
+                    inputs = candidate;
+                } else {
+                    start = end;
+                }
+            }
+            chunk_size /= 2;
+        }
+    }
+
+    if minimiser.runs >= max_runs {
+        log::warn!(
+            "Reached the limit of {} runs, the replay may get even shorter.",
+            max_runs
+        );
+    }
+
+    minimiser.write(output_path, &inputs)?;
+    let _ = std::fs::remove_file(&minimiser.candidate_path);
+
+    Ok(Summary {
+        original_inputs,
+        minimised_inputs: inputs.len(),
+        runs: minimiser.runs,
+    })
+}
+
+thread_local! {
+    /// Whether this thread is playing a candidate back right now.
+    static PLAYING_CANDIDATE: Cell<bool> = const { Cell::new(false) };
+
+    /// The panic of the candidate this thread played back last.
+    static CANDIDATE_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Keep the panics of the candidates out of the log and remember them
+/// instead. The panics of everything else go to the previous hook.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if PLAYING_CANDIDATE.with(Cell::get) {
+                CANDIDATE_PANIC.with(|panic| *panic.borrow_mut() = Some(info.to_string()));
+            } else {
+                previous_hook(info);
+            }
+        }));
+    });
+}
+
+struct Minimiser {
+    header: ReplayHeader,
+    failure: Failure,
+    candidate_path: PathBuf,
+    /// The panic message and location of the whole replay.
+    original_panic: Option<String>,
+    runs: usize,
+}
+
+impl Minimiser {
+    fn reproduces(&mut self, inputs: &[Input]) -> Result<bool, Box<dyn Error>> {
+        self.runs += 1;
+        self.write(&self.candidate_path, inputs)?;
+        let state = State::load_replay_for_tools(&self.candidate_path)?;
+
+        PLAYING_CANDIDATE.with(|playing| playing.set(true));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            headless::run(NoOpStore, Box::new(state), None)
+        }));
+        PLAYING_CANDIDATE.with(|playing| playing.set(false));
+        let panic = CANDIDATE_PANIC.with(|panic| panic.borrow_mut().take());
+
+        let reproduces = match (self.failure, result) {
+            (Failure::Panic, Err(_payload)) => {
+                let panic = panic.unwrap_or_default();
+                match &self.original_panic {
+                    Some(original_panic) => panic == *original_panic,
+                    None => {
+                        log::info!("The replay {}", panic);
+                        self.original_panic = Some(panic);
+                        true
+                    }
+                }
+            }
+            (Failure::Desync, Ok(Err(ReplayError::Desync(_)))) => true,
+            (Failure::CauseOfDeath(cause), Ok(Ok(final_state))) => {
+                formula::cause_of_death(&final_state.player) == Some(cause)
+            }
+            _ => false,
+        };
+        log::debug!(
+            "Run {} with {} inputs reproduces the failure: {}",
+            self.runs,
+            inputs.len(),
+            reproduces
+        );
+        Ok(reproduces)
+    }
+
+    /// Write the inputs as a replay. The ticks are renumbered so
+    /// they're consecutive again.
+    fn write(&self, path: &Path, inputs: &[Input]) -> Result<(), Box<dyn Error>> {
+        let inputs: Vec<Input> = inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| Input {
+                tick_id: index as i32 + 1,
+                ..input.clone()
+            })
+            .collect();
+        state::write_replay(path, &self.header, &inputs)
+    }
+}
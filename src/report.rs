@@ -0,0 +1,87 @@
+use crate::{
+    formula, item,
+    monster::{self, CompanionBonus},
+    player::{CauseOfDeath, Mind},
+    state::{Side, State},
+};
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Things that happened during the playthrough that the game itself
+/// doesn't need to keep track of.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct RunStats {
+    pub items_used: BTreeMap<item::Kind, i32>,
+    /// Monsters killed by the player, either by bumping into them or
+    /// in a dose explosion.
+    pub monsters_killed: BTreeMap<monster::Kind, i32>,
+    pub turns_in_withdrawal: i32,
+    pub turns_sober: i32,
+    pub turns_high: i32,
+    /// The bonus of every NPC that started accompanying the player,
+    /// in the order they were met.
+    pub companions_met: Vec<CompanionBonus>,
+}
+
+impl RunStats {
+    pub fn record_item_use(&mut self, kind: item::Kind) {
+        *self.items_used.entry(kind).or_insert(0) += 1;
+    }
+
+    pub fn record_kill(&mut self, kind: monster::Kind) {
+        *self.monsters_killed.entry(kind).or_insert(0) += 1;
+    }
+
+    pub fn record_turn(&mut self, mind: Mind) {
+        match mind {
+            Mind::Withdrawal(_) => self.turns_in_withdrawal += 1,
+            Mind::Sober(_) => self.turns_sober += 1,
+            Mind::High(_) => self.turns_high += 1,
+        }
+    }
+}
+
+/// Summary of a finished game, written out as JSON by `--report`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    pub seed: u32,
+    pub turns: i32,
+    pub ticks: i32,
+    pub victory: bool,
+    pub items_used: BTreeMap<item::Kind, i32>,
+    pub monsters_killed: BTreeMap<monster::Kind, i32>,
+    pub turns_in_withdrawal: i32,
+    pub turns_sober: i32,
+    pub turns_high: i32,
+    pub longest_high_streak: i32,
+    pub companions_met: Vec<CompanionBonus>,
+    pub cause_of_death: Option<CauseOfDeath>,
+    /// The monster that killed the player (if any).
+    pub perpetrator: Option<monster::Kind>,
+    pub will: i32,
+}
+
+impl Report {
+    pub fn new(state: &State) -> Self {
+        let player = &state.player;
+        let stats = player.run_stats.clone();
+        Self {
+            seed: state.seed,
+            turns: state.turn,
+            ticks: state.tick_id,
+            victory: state.side == Side::Victory,
+            items_used: stats.items_used,
+            monsters_killed: stats.monsters_killed,
+            turns_in_withdrawal: stats.turns_in_withdrawal,
+            turns_sober: stats.turns_sober,
+            turns_high: stats.turns_high,
+            longest_high_streak: player.longest_high_streak,
+            companions_met: stats.companions_met,
+            cause_of_death: formula::cause_of_death(player),
+            perpetrator: player.perpetrator.as_ref().map(|monster| monster.kind),
+            will: player.will.to_int(),
+        }
+    }
+}
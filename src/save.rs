@@ -0,0 +1,762 @@
+use crate::{
+    player::Mind,
+    state::{self, Challenge, State},
+};
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    error::Error,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+pub const SAVE_EXTENSION: &str = "sav";
+
+/// The per-user directory the game keeps its data in:
+///
+/// * Linux: `$XDG_DATA_HOME/dose-response` (`~/.local/share/dose-response`)
+/// * macOS: `~/Library/Application Support/Dose Response`
+/// * Windows: `%APPDATA%\Dose Response`
+///
+/// Falls back to the working directory if there's no home directory.
+pub fn data_dir() -> PathBuf {
+    let home = std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from);
+    let dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(|appdata| PathBuf::from(appdata).join("Dose Response"))
+    } else if cfg!(target_os = "macos") {
+        home.map(|home| home.join("Library/Application Support/Dose Response"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home.map(|home| home.join(".local/share")))
+            .map(|dir| dir.join("dose-response"))
+    };
+    dir.unwrap_or_else(|| {
+        log::warn!("Could not find the home directory. Using the working directory instead.");
+        PathBuf::from(".")
+    })
+}
+
+/// What the save slot list shows. It's stored in front of the game
+/// state so we don't have to load the whole game to show it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub name: String,
+    /// Local date and time of the save: `2022-01-31 18:05`.
+    pub date: String,
+    pub turn: i32,
+    pub will: i32,
+    /// `Withdrawal`, `Sober` or `High`.
+    pub mind: String,
+    pub seed: u32,
+    pub challenge: Challenge,
+}
+
+impl Metadata {
+    pub fn new(name: &str, state: &State) -> Self {
+        let mind = match state.player.mind {
+            Mind::Withdrawal(_) => "Withdrawal",
+            Mind::Sober(_) => "Sober",
+            Mind::High(_) => "High",
+        };
+        Metadata {
+            name: name.into(),
+            date: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            turn: state.turn,
+            will: state.player.will.to_int(),
+            mind: mind.into(),
+            seed: state.seed,
+            challenge: state.challenge,
+        }
+    }
+}
+
+/// A saved game.
+#[derive(Clone, Debug)]
+pub struct Slot {
+    pub path: PathBuf,
+    pub metadata: Metadata,
+}
+
+/// The save slots in a directory. Every slot is a single
+/// `<name>.sav` file.
+#[derive(Clone, Debug)]
+pub struct Slots {
+    dir: PathBuf,
+}
+
+impl Slots {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Slots { dir: dir.into() }
+    }
+
+    /// The slots in the `saves` directory of `data_dir`.
+    pub fn in_data_dir() -> Self {
+        Self::new(data_dir().join("saves"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name).with_extension(SAVE_EXTENSION)
+    }
+
+    /// The first `slot-<number>` that isn't taken.
+    pub fn next_free_name(&self) -> String {
+        (1..)
+            .map(|number| format!("slot-{}", number))
+            .find(|name| !self.path(name).exists())
+            .unwrap_or_default()
+    }
+
+    /// All the saved games, the most recent one first. The files we
+    /// can't read are skipped.
+    pub fn list(&self) -> Result<Vec<Slot>, Box<dyn Error>> {
+        let mut paths = vec![];
+        if self.dir.exists() {
+            for entry in fs::read_dir(&self.dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|ext| ext.to_str()) == Some(SAVE_EXTENSION) {
+                    paths.push(path);
+                }
+            }
+        }
+        let legacy_path = Path::new(LEGACY_SAVE_PATH);
+        if legacy_path.exists() {
+            paths.push(legacy_path.into());
+        }
+
+        let mut slots = vec![];
+        for path in paths {
+            if is_autosave(&path) {
+                continue;
+            }
+            match read_metadata(&path) {
+                Ok(metadata) => slots.push(Slot { path, metadata }),
+                Err(err) => log::warn!("Skipping the save '{}': {}", path.display(), err),
+            }
+        }
+        slots.sort_by(|a, b| {
+            b.metadata
+                .date
+                .cmp(&a.metadata.date)
+                .then_with(|| a.metadata.name.cmp(&b.metadata.name))
+        });
+        Ok(slots)
+    }
+
+    /// Save the game into the slot called `name`, replacing whatever
+    /// was there.
+    pub fn save(&self, name: &str, state: &State) -> Result<PathBuf, Box<dyn Error>> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            throw!(&format!(
+                "Invalid save slot name: `{}`. Use letters, numbers, `-` and `_`.",
+                name
+            ));
+        }
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(name);
+        write(&path, &Metadata::new(name, state), state)?;
+        log::info!("Saved the game into: {}", path.display());
+        Ok(path)
+    }
+
+    fn autosave_paths(&self) -> Vec<PathBuf> {
+        (1..=AUTOSAVE_SLOTS)
+            .map(|number| self.path(&format!("{}{}", AUTOSAVE_PREFIX, number)))
+            .collect()
+    }
+
+    /// Save the game into the autosave slot that's free or was
+    /// written the longest time ago.
+    pub fn autosave(&self, state: &State) -> Result<PathBuf, Box<dyn Error>> {
+        let oldest = self
+            .autosave_paths()
+            .into_iter()
+            .min_by_key(|path| fs::metadata(path).and_then(|m| m.modified()).ok());
+        let name = match oldest.as_ref().and_then(|path| path.file_stem()) {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => throw!("There are no autosave slots."),
+        };
+        self.save(&name, state)
+    }
+
+    /// The most recent autosave.
+    pub fn latest_autosave(&self) -> Option<Slot> {
+        let path = self
+            .autosave_paths()
+            .into_iter()
+            .filter_map(|path| {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+                Some((modified, path))
+            })
+            .max()?
+            .1;
+        match read_metadata(&path) {
+            Ok(metadata) => Some(Slot { path, metadata }),
+            Err(err) => {
+                log::warn!("Can't read the autosave '{}': {}", path.display(), err);
+                None
+            }
+        }
+    }
+
+    /// Delete all the autosaves. They're only there in case the game
+    /// crashes, not to let the player go back in time.
+    pub fn remove_autosaves(&self) {
+        for path in self.autosave_paths() {
+            if path.exists() {
+                if let Err(error) = fs::remove_file(&path) {
+                    log::error!(
+                        "Failed to delete the autosave '{}'. Error: {:?}",
+                        path.display(),
+                        error
+                    );
+                }
+            }
+        }
+    }
+
+    /// Mark the game as running until `end_session` is called.
+    /// Returns `true` if the previous session never got to call it,
+    /// i.e. the game crashed or was killed.
+    pub fn begin_session(&self) -> Result<bool, Box<dyn Error>> {
+        let marker = self.dir.join(SESSION_MARKER);
+        let unclean_exit = marker.exists();
+        if unclean_exit {
+            log::warn!("The previous session didn't exit cleanly.");
+        }
+        fs::create_dir_all(&self.dir)?;
+        fs::write(&marker, std::process::id().to_string())?;
+        Ok(unclean_exit)
+    }
+
+    /// The game is quitting normally. Nothing will need to be
+    /// recovered so this removes the autosaves too.
+    ///
+    /// Does nothing if this process didn't begin the session (e.g.
+    /// it's playing a replay).
+    pub fn end_session(&self) {
+        let marker = self.dir.join(SESSION_MARKER);
+        let pid = std::process::id().to_string();
+        if fs::read_to_string(&marker).ok().as_deref() != Some(pid.as_str()) {
+            return;
+        }
+        self.remove_autosaves();
+        if let Err(error) = fs::remove_file(&marker) {
+            log::error!(
+                "Failed to delete the session marker '{}'. Error: {:?}",
+                marker.display(),
+                error
+            );
+        }
+    }
+}
+
+/// How many autosave slots the game rotates through. If the game
+/// crashes while writing one, the previous one is still there.
+pub const AUTOSAVE_SLOTS: u32 = 3;
+
+const AUTOSAVE_PREFIX: &str = "autosave-";
+
+/// Exists in the saves directory while the game is running.
+const SESSION_MARKER: &str = "session.lock";
+
+fn is_autosave(path: &Path) -> bool {
+    path.file_stem()
+        .is_some_and(|stem| stem.to_string_lossy().starts_with(AUTOSAVE_PREFIX))
+}
+
+/// The start of every save file.
+pub const MAGIC: &[u8; 8] = b"DOSESAVE";
+
+/// The layout of the save files and of everything in them.
+///
+/// Bump this whenever `State` (or anything it contains) serialises
+/// differently and add a migration from the previous version to
+/// `MIGRATIONS`.
+///
+/// 1. `SAVEDGAME.sav`: the game version, commit and `State` in
+///    bincode. No magic, checksum or compression.
+/// 2. `MAGIC`, the format version, a blake3 checksum of the rest of
+///    the file and the gzipped `Header` and `State` in bincode.
+pub const SAVE_FORMAT_VERSION: u32 = 2;
+
+/// `MIGRATIONS[n]` upgrades a save from format `n + 1` to
+/// `SAVE_FORMAT_VERSION`.
+///
+/// A migration gets the uncompressed body of the save: the header and
+/// the state in bincode. Copy the old versions of the structs that
+/// changed into a `v<N>` module, deserialise the body with them and
+/// build the current ones field by field.
+const MIGRATIONS: [Migration; 1] = [from_v1];
+
+type Migration = fn(RawSave) -> Result<RawSave, Box<dyn Error>>;
+
+/// The save written by older versions of the game into the working
+/// directory. It's listed and loaded like the other slots.
+pub const LEGACY_SAVE_PATH: &str = "SAVEDGAME.sav";
+
+/// What's in front of the game state.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    /// The game version that wrote the save.
+    pub version: String,
+    pub commit: String,
+    pub metadata: Metadata,
+    /// The replay the game was being recorded into. The loaded game
+    /// keeps recording there.
+    pub replay_path: Option<PathBuf>,
+}
+
+/// A save that hasn't been deserialised yet.
+struct RawSave {
+    format_version: u32,
+    /// The name of the slot. Format 1 doesn't store it anywhere else.
+    name: String,
+    /// The header and the state in bincode.
+    body: Vec<u8>,
+}
+
+/// The structs as they were in format 1, the `SAVEDGAME.sav` written
+/// before there was a save format.
+///
+/// NOTE: these are the exact layouts the older versions of the game
+/// wrote. bincode doesn't store the field names or types, only the
+/// values in order, so never change them. The structs that haven't
+/// changed since are used as they are. When one of them changes,
+/// copy its old version here.
+pub(crate) mod v1 {
+    use crate::{
+        animation::{self, ScreenFade},
+        engine::Mouse,
+        graphic::Graphic,
+        item::Item,
+        keys::Keys,
+        monster::{CompanionBonus, Monster},
+        palette::Palette,
+        pathfinding::Path,
+        player::{Bonus, Mind},
+        point::Point,
+        random::Random,
+        ranged_int::Ranged,
+        state::{Challenge, Command, GameSession, Side},
+        timer::Timer,
+        window::Window,
+        windows::{self, Windows},
+        world::{Chunk, ChunkPosition, MonsterId},
+    };
+
+    use std::{
+        collections::{HashMap, VecDeque},
+        time::Duration,
+    };
+
+    use serde::Deserialize;
+
+    /// The player before the run stats.
+    #[derive(Deserialize)]
+    pub struct Player {
+        pub mind: Mind,
+        pub will: Ranged,
+        pub tolerance: i32,
+        pub panic: Ranged,
+        pub stun: Ranged,
+        pub pos: Point,
+        pub motion_animation: animation::Move,
+        pub color_index: usize,
+        pub graphic: Graphic,
+        pub inventory: Vec<Item>,
+        pub anxiety_counter: Ranged,
+        pub bonus: Bonus,
+        pub bonuses: Vec<CompanionBonus>,
+        pub current_high_streak: i32,
+        pub longest_high_streak: i32,
+        pub reset_count: i32,
+        pub dead: bool,
+        pub invincible: bool,
+        pub perpetrator: Option<Monster>,
+        pub ap: i32,
+    }
+
+    /// The world before the landscapes and regions.
+    #[derive(Deserialize)]
+    pub struct World {
+        pub seed: u32,
+        pub max_half_size: i32,
+        pub chunk_size: i32,
+        pub chunks: HashMap<ChunkPosition, Chunk>,
+        pub challenge: Challenge,
+    }
+
+    /// The state before the command stream.
+    #[derive(Deserialize)]
+    pub struct State {
+        pub player: Player,
+        pub world_size: Point,
+        pub world: World,
+        pub map_size: Point,
+        pub panel_width: i32,
+        pub screen_position_in_world: Point,
+        pub seed: u32,
+        pub rng: Random,
+        pub audio_rng: Random,
+        pub keys: Keys,
+        pub mouse: Mouse,
+        pub commands: VecDeque<Command>,
+        pub player_path: Path,
+        pub side: Side,
+        pub turn: i32,
+        pub tick_id: i32,
+        pub previous_tick: i32,
+        pub cheating: bool,
+        pub replay: bool,
+        pub replay_full_speed: bool,
+        pub exit_after: bool,
+        pub debug: bool,
+        pub clock: Duration,
+        pub replay_step: Duration,
+        pub pos_timer: Timer,
+        pub path_walking_timer: Timer,
+        pub paused: bool,
+        pub old_screen_pos: Point,
+        pub new_screen_pos: Point,
+        pub screen_fading: Option<ScreenFade>,
+        pub offset_px: Point,
+        pub game_session: GameSession,
+        pub victory_npc_id: Option<MonsterId>,
+        pub window_stack: Windows<Window>,
+        pub show_keyboard_movement_hints: bool,
+        pub show_anxiety_counter: bool,
+        pub player_picked_up_a_dose: bool,
+        pub player_bumped_into_a_monster: bool,
+        pub current_help_window: windows::help::Page,
+        pub inventory_focused: bool,
+        pub keyboard_scroll_delta: [f32; 2],
+        pub show_endscreen_and_uncover_map_during_fadein: bool,
+        pub uncovered_map: bool,
+        pub challenge: Challenge,
+        pub palette: Palette,
+    }
+}
+
+/// Format 1 didn't have the run stats or the command stream. It
+/// doesn't have anything that isn't in the save either, so that's all
+/// left empty.
+impl From<v1::State> for State {
+    fn from(state: v1::State) -> Self {
+        State {
+            player: state.player.into(),
+            explosion_animation: None,
+            extra_animations: vec![],
+            world_size: state.world_size,
+            world: state.world.into(),
+            map_size: state.map_size,
+            panel_width: state.panel_width,
+            screen_position_in_world: state.screen_position_in_world,
+            seed: state.seed,
+            rng: state.rng,
+            audio_rng: state.audio_rng,
+            keys: state.keys,
+            mouse: state.mouse,
+            inputs: VecDeque::new(),
+            commands: state.commands,
+            player_path: state.player_path,
+            input_logger: Box::new(io::sink()),
+            recorded_commands: vec![],
+            commands_executed: 0,
+            command_logger: Box::new(io::sink()),
+            recording_commands: false,
+            side: state.side,
+            turn: state.turn,
+            tick_id: state.tick_id,
+            previous_tick: state.previous_tick,
+            cheating: state.cheating,
+            replay: state.replay,
+            replay_full_speed: state.replay_full_speed,
+            exit_after: state.exit_after,
+            debug: state.debug,
+            clock: state.clock,
+            replay_step: state.replay_step,
+            stats: Default::default(),
+            pos_timer: state.pos_timer,
+            path_walking_timer: state.path_walking_timer,
+            autoplay: false,
+            autoplay_timer: state::autoplay_timer(),
+            paused: state.paused,
+            old_screen_pos: state.old_screen_pos,
+            new_screen_pos: state.new_screen_pos,
+            screen_fading: state.screen_fading,
+            offset_px: state.offset_px,
+            game_session: state.game_session,
+            victory_npc_id: state.victory_npc_id,
+            window_stack: state.window_stack,
+            show_keyboard_movement_hints: state.show_keyboard_movement_hints,
+            show_anxiety_counter: state.show_anxiety_counter,
+            player_picked_up_a_dose: state.player_picked_up_a_dose,
+            player_bumped_into_a_monster: state.player_bumped_into_a_monster,
+            selected_menu_action: None,
+            selected_settings_position: None,
+            selected_endgame_window_action: None,
+            selected_sidebar_action: None,
+            save_slots: vec![],
+            selected_save_slot: 0,
+            seed_text: String::new(),
+            seed_error: None,
+            save_slot: None,
+            recoverable_session: None,
+            current_help_window: state.current_help_window,
+            inventory_focused: state.inventory_focused,
+            keyboard_scroll_delta: state.keyboard_scroll_delta,
+            show_endscreen_and_uncover_map_during_fadein: state
+                .show_endscreen_and_uncover_map_during_fadein,
+            uncovered_map: state.uncovered_map,
+            challenge: state.challenge,
+            palette: state.palette,
+            replay_header: None,
+            replay_path: None,
+            replay_snapshots: BTreeMap::new(),
+            replay_seek: None,
+            replay_speed: Default::default(),
+            replay_tick_frames: 0,
+            replay_ticks: None,
+            replay_error: None,
+        }
+    }
+}
+
+/// No header, no metadata and the state from before the run stats
+/// and the command stream.
+fn from_v1(save: RawSave) -> Result<RawSave, Box<dyn Error>> {
+    let mut reader = &save.body[..];
+    let version = bincode::deserialize_from(&mut reader)?;
+    let commit = bincode::deserialize_from(&mut reader)?;
+    let state: v1::State = bincode::deserialize_from(&mut reader)?;
+    let state = State::from(state);
+    let header = Header {
+        version,
+        commit,
+        metadata: Metadata::new(&save.name, &state),
+        replay_path: None,
+    };
+    let mut body = bincode::serialize(&header)?;
+    bincode::serialize_into(&mut body, &state)?;
+    Ok(RawSave {
+        format_version: SAVE_FORMAT_VERSION,
+        body,
+        ..save
+    })
+}
+
+fn write(path: &Path, metadata: &Metadata, state: &State) -> Result<(), Box<dyn Error>> {
+    // NOTE: the replays go into a directory relative to the working
+    // one. The game can be started from somewhere else next time.
+    let replay_path = state
+        .replay_path
+        .as_ref()
+        .map(|replay_path| fs::canonicalize(replay_path).unwrap_or_else(|_| replay_path.clone()));
+    let header = Header {
+        version: crate::metadata::VERSION.into(),
+        commit: crate::metadata::GIT_HASH.into(),
+        metadata: metadata.clone(),
+        replay_path,
+    };
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    bincode::serialize_into(&mut encoder, &header)?;
+    bincode::serialize_into(&mut encoder, state)?;
+    let body = encoder.finish()?;
+
+    // NOTE: write into a temporary file first so a crash halfway
+    // through doesn't destroy the previous save.
+    let temporary_path = path.with_extension("tmp");
+    {
+        let mut file = BufWriter::new(File::create(&temporary_path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&SAVE_FORMAT_VERSION.to_le_bytes())?;
+        file.write_all(blake3::hash(&body).as_bytes())?;
+        file.write_all(&body)?;
+        file.flush()?;
+    }
+    fs::rename(&temporary_path, path)?;
+    Ok(())
+}
+
+/// What comes between `MAGIC` and the gzipped body.
+struct Preamble {
+    format_version: u32,
+    /// blake3 hash of the rest of the file.
+    checksum: [u8; 32],
+}
+
+/// Read the start of the file. `None` if it's a format 1 save which
+/// doesn't have the preamble.
+fn read_preamble<R: Read>(reader: &mut R) -> Result<Option<Preamble>, Box<dyn Error>> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Ok(None);
+    }
+    let mut format_version = [0; 4];
+    reader.read_exact(&mut format_version)?;
+    let format_version = u32::from_le_bytes(format_version);
+    if format_version > SAVE_FORMAT_VERSION {
+        throw!(&format!(
+            "The game was saved by a newer release (save format {}). This release can only load formats up to {}.",
+            format_version, SAVE_FORMAT_VERSION
+        ));
+    }
+    let mut checksum = [0; 32];
+    reader.read_exact(&mut checksum)?;
+    Ok(Some(Preamble {
+        format_version,
+        checksum,
+    }))
+}
+
+fn read_raw(path: &Path) -> Result<RawSave, Box<dyn Error>> {
+    let mut contents = vec![];
+    File::open(path)?.read_to_end(&mut contents)?;
+    let mut reader = &contents[..];
+    let name = path
+        .file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+
+    match read_preamble(&mut reader)? {
+        Some(Preamble {
+            format_version,
+            checksum,
+        }) => {
+            if blake3::hash(reader) != blake3::Hash::from(checksum) {
+                throw!("The save file is corrupted: its checksum doesn't match.");
+            }
+            let mut body = vec![];
+            GzDecoder::new(reader).read_to_end(&mut body)?;
+            Ok(RawSave {
+                format_version,
+                name,
+                body,
+            })
+        }
+        None => Ok(RawSave {
+            format_version: 1,
+            name,
+            body: contents,
+        }),
+    }
+}
+
+/// Upgrade the save to `SAVE_FORMAT_VERSION`.
+fn migrate(save: RawSave) -> Result<RawSave, Box<dyn Error>> {
+    if save.format_version == SAVE_FORMAT_VERSION {
+        return Ok(save);
+    }
+    let migration = match (save.format_version as usize)
+        .checked_sub(1)
+        .and_then(|index| MIGRATIONS.get(index))
+    {
+        Some(migration) => migration,
+        None => throw!(&format!(
+            "There's no migration from save format {}.",
+            save.format_version
+        )),
+    };
+    log::info!(
+        "Migrating the save from format {} to {}",
+        save.format_version,
+        SAVE_FORMAT_VERSION
+    );
+    migration(save)
+}
+
+/// Read the header of the save without loading the game.
+pub fn read_header(path: &Path) -> Result<Header, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    match read_preamble(&mut reader)? {
+        Some(Preamble {
+            format_version: SAVE_FORMAT_VERSION,
+            ..
+        }) => Ok(bincode::deserialize_from(GzDecoder::new(reader))?),
+        // NOTE: an older save may not have the header we need. This
+        // only happens once, until it's loaded and saved again.
+        _ => {
+            let save = migrate(read_raw(path)?)?;
+            Ok(bincode::deserialize_from(&save.body[..])?)
+        }
+    }
+}
+
+pub fn read_metadata(path: &Path) -> Result<Metadata, Box<dyn Error>> {
+    Ok(read_header(path)?.metadata)
+}
+
+/// Read the saved game, checking it isn't corrupted and upgrading it
+/// from older formats.
+pub fn read(path: &Path) -> Result<(Header, State), Box<dyn Error>> {
+    let save = migrate(read_raw(path)?)?;
+    let mut reader = &save.body[..];
+    let header: Header = bincode::deserialize_from(&mut reader)?;
+    log::info!(
+        "Save format {}, version {}, commit {}",
+        save.format_version,
+        header.version,
+        header.commit
+    );
+    if header.version != crate::metadata::VERSION {
+        log::info!(
+            "The game was saved in a different version: {}. This release has version: {}.",
+            header.version,
+            crate::metadata::VERSION
+        );
+    }
+    if header.commit != crate::metadata::GIT_HASH {
+        log::info!(
+            "The game was saved in a different commit: {}. This release has commit: {}.",
+            header.commit,
+            crate::metadata::GIT_HASH
+        );
+    }
+    let state = bincode::deserialize(reader)?;
+    Ok((header, state))
+}
+
+/// Load the saved game and delete its file. Like in any other
+/// roguelike, a save lets you stop playing, not go back in time.
+///
+/// The game keeps recording into the replay it was recorded into
+/// before it was saved.
+pub fn load(path: &Path) -> Result<State, Box<dyn Error>> {
+    let (header, mut state) = read(path)?;
+    log::info!("Loaded the save: {:?}", header.metadata);
+
+    if let Some(replay_path) = &header.replay_path {
+        if let Err(error) = state.resume_replay(replay_path) {
+            log::warn!(
+                "Can't continue the replay '{}', the loaded game won't be recorded: {}",
+                replay_path.display(),
+                error
+            );
+        }
+    }
+
+    if let Err(error) = fs::remove_file(path) {
+        log::error!(
+            "Failed to delete the successfully loaded savegame. Error: {:?}",
+            error
+        );
+    }
+
+    Ok(state)
+}
@@ -0,0 +1,269 @@
+use crate::{
+    audio::Audio,
+    engine, formula, game,
+    generators::Landscape,
+    item,
+    level::TileKind,
+    monster,
+    player::{CauseOfDeath, Mind},
+    point::Point,
+    state::{Challenge, Command, GameSession as Session, Side, State},
+};
+
+use std::{collections::BTreeMap, error::Error, time::Duration};
+
+use serde::Serialize;
+
+/// How long a tick takes. The same as in the headless mode.
+const TICK_DURATION: Duration = Duration::from_millis(16);
+
+/// NOTE: a step is normally over in a handful of ticks. This is just
+/// a safeguard so a bug can't hang the caller.
+const MAX_TICKS_PER_STEP: i32 = 10_000;
+
+/// NOTE: every command should do something. If the policy keeps
+/// picking ones that don't, it's stuck and the game would never end.
+const MAX_IDLE_STEPS: i32 = 100;
+
+/// NOTE: without `one_chance`, the player comes back instead of
+/// dying so a policy could keep playing forever. The real games end
+/// long before this.
+const MAX_TURNS: i32 = 10_000;
+
+/// Whether the game is still going on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Outcome {
+    Running,
+    Victory,
+    /// The player died and the challenge only gives them one chance.
+    GameOver,
+}
+
+/// What happened after the player's command.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StepResult {
+    /// Whether the player spent an action point on the command.
+    /// E.g. walking into a tree or using an item the player doesn't
+    /// have does nothing.
+    pub acted: bool,
+    /// Set when the player died during the step. Unless this ended
+    /// the game, they start over at the same place.
+    pub death: Option<CauseOfDeath>,
+    pub outcome: Outcome,
+}
+
+/// Everything the player can see.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Observation {
+    pub turn: i32,
+    pub outcome: Outcome,
+    pub player_pos: Point,
+    /// `Withdrawal`, `Sober` or `High`.
+    pub mind: String,
+    /// How far along the current state of mind the player is.
+    pub mind_value: i32,
+    pub will: i32,
+    pub tolerance: i32,
+    pub panic: i32,
+    pub stun: i32,
+    pub action_points: i32,
+    pub inventory: BTreeMap<item::Kind, i32>,
+    /// How far the player can see. Everything below is within this
+    /// radius.
+    pub exploration_radius: i32,
+    pub trees: Vec<Point>,
+    pub items: Vec<(Point, item::Kind)>,
+    pub monsters: Vec<(Point, monster::Kind)>,
+}
+
+/// A game driven by commands rather than keys and the mouse. It runs
+/// the same rules as the real game, just without any window, sound
+/// or replay.
+pub struct GameSession {
+    state: Box<State>,
+    audio: Audio,
+}
+
+impl GameSession {
+    pub fn new(seed: u32, challenge: Challenge) -> Self {
+        Self::with_landscape(seed, challenge, Landscape::default())
+    }
+
+    pub fn with_landscape(seed: u32, challenge: Challenge, landscape: Landscape) -> Self {
+        Self {
+            state: Box::new(State::new_session(seed, challenge, landscape)),
+            audio: Audio::new(None),
+        }
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        if self.state.side == Side::Victory {
+            Outcome::Victory
+        } else if self.state.game_session == Session::Ended {
+            Outcome::GameOver
+        } else {
+            Outcome::Running
+        }
+    }
+
+    /// Carry out the command and let the game run until the player
+    /// can act again (or the game's over).
+    pub fn step(&mut self, command: Command) -> StepResult {
+        let mut result = StepResult {
+            acted: false,
+            death: None,
+            outcome: self.outcome(),
+        };
+        if result.outcome != Outcome::Running {
+            return result;
+        }
+
+        self.state.commands.push_back(command);
+        for _ in 0..MAX_TICKS_PER_STEP {
+            self.tick(&mut result);
+            result.outcome = self.outcome();
+            let ready = self.state.commands.is_empty()
+                && self.state.player.has_ap(1)
+                && self.state.explosion_animation.is_none();
+            if ready || result.outcome != Outcome::Running {
+                return result;
+            }
+        }
+
+        log::warn!(
+            "The command didn't finish in {} ticks, giving up.",
+            MAX_TICKS_PER_STEP
+        );
+        self.state.commands.clear();
+        result
+    }
+
+    /// Keep carrying out the commands `policy` picks until the game's
+    /// over. Gives up after `MAX_TURNS` turns.
+    pub fn play(
+        &mut self,
+        mut policy: impl FnMut(&State) -> Command,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut idle_steps = 0;
+        while self.outcome() == Outcome::Running {
+            let command = policy(&self.state);
+            if self.step(command).acted {
+                idle_steps = 0;
+            } else {
+                idle_steps += 1;
+            }
+            if idle_steps >= MAX_IDLE_STEPS {
+                throw!(&format!(
+                    "The player got stuck at turn {}.",
+                    self.state.turn
+                ));
+            }
+            if self.state.turn >= MAX_TURNS {
+                throw!(&format!("The game didn't end in {} turns.", MAX_TURNS));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn observation(&self) -> Observation {
+        let state = &self.state;
+        let player = &state.player;
+        let radius = formula::exploration_radius(player.mind);
+
+        let mut inventory = BTreeMap::new();
+        for item in &player.inventory {
+            *inventory.entry(item.kind).or_insert(0) += 1;
+        }
+
+        let mut trees = vec![];
+        let mut items = vec![];
+        for y in -radius..=radius {
+            for x in -radius..=radius {
+                let pos = player.pos + (x, y);
+                if !pos.inside_circular_area(player.pos, radius) {
+                    continue;
+                }
+                if let Some(cell) = state.world.cell(pos) {
+                    if cell.tile.kind == TileKind::Tree {
+                        trees.push(pos);
+                    }
+                    items.extend(cell.items.iter().map(|item| (pos, item.kind)));
+                }
+            }
+        }
+
+        let mut monsters: Vec<(Point, monster::Kind)> = state
+            .world
+            .monsters(formula::simulation_area(player.pos))
+            .filter(|monster| {
+                !monster.dead && monster.position.inside_circular_area(player.pos, radius)
+            })
+            .map(|monster| (monster.position, monster.kind))
+            .collect();
+        // NOTE: `world.monsters` doesn't have a stable order.
+        monsters.sort_by_key(|&(pos, kind)| (pos.x, pos.y, kind));
+
+        Observation {
+            turn: state.turn,
+            outcome: self.outcome(),
+            player_pos: player.pos,
+            mind: player.mind.to_string(),
+            mind_value: match player.mind {
+                Mind::Withdrawal(value) | Mind::Sober(value) | Mind::High(value) => value.to_int(),
+            },
+            will: player.will.to_int(),
+            tolerance: player.tolerance,
+            panic: player.panic.to_int(),
+            stun: player.stun.to_int(),
+            action_points: player.ap(),
+            inventory,
+            exploration_radius: radius,
+            trees,
+            items,
+            monsters,
+        }
+    }
+
+    /// The rules part of `game::process_game`.
+    fn tick(&mut self, result: &mut StepResult) {
+        let state = &mut self.state;
+        let audio = &mut self.audio;
+        let tile_size = engine::DEFAULT_TILE_SIZE;
+
+        state.clock += TICK_DURATION;
+        state.previous_tick = state.tick_id;
+        state.tick_id += 1;
+
+        let player_was_alive = state.player.alive();
+        let simulation_area = formula::simulation_area(state.player.pos);
+        let mut entire_turn_ended = false;
+        if state.side != Side::Victory {
+            let player_ap = state.player.ap();
+            if player_ap >= 1 {
+                game::process_player(state, tile_size, audio, simulation_area);
+                result.acted |= state.player.ap() < player_ap;
+            }
+            entire_turn_ended =
+                game::process_monsters_turn(state, audio, tile_size, simulation_area, player_ap);
+        }
+
+        let death = game::finish_tick(
+            state,
+            audio,
+            simulation_area,
+            entire_turn_ended,
+            player_was_alive,
+            TICK_DURATION,
+        );
+        result.death = result.death.or(death);
+
+        // NOTE: nothing's going to show these so don't let them pile up.
+        state.extra_animations.clear();
+        state.screen_fading = None;
+        audio.play_mixed_sound_effects(&mut state.audio_rng);
+    }
+}
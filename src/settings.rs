@@ -38,6 +38,9 @@ pub const FAST_DEPRESSION: &str = "fast_depression";
 pub const PERMADEATH: &str = "permadeath";
 pub const BACKGROUND_VOLUME: &str = "background_volume";
 pub const SOUND_VOLUME: &str = "sound_volume";
+pub const AUTOSAVE_TURNS: &str = "autosave_turns";
+
+pub const DEFAULT_AUTOSAVE_TURNS: i32 = 50;
 
 pub const FIRST_EVER_STARTUP: &str = "first_ever_startup";
 
@@ -84,6 +87,8 @@ pub struct Settings {
     pub permadeath: bool,
     pub background_volume: f32,
     pub sound_volume: f32,
+    /// Autosave every this many turns. `0` turns it off.
+    pub autosave_turns: i32,
     pub first_ever_startup: bool,
 }
 
@@ -110,6 +115,7 @@ impl Default for Settings {
             permadeath: true,
             background_volume: 1.0,
             sound_volume: 1.0,
+            autosave_turns: DEFAULT_AUTOSAVE_TURNS,
             first_ever_startup: true,
         };
 
@@ -217,6 +223,8 @@ impl Settings {
         );
         out.push_str("# Options: <0.0, 1.0>\n");
         let _ = writeln!(out, "{} = \"{}\"", SOUND_VOLUME, self.sound_volume);
+        out.push_str("# Options: 0 (off) or more\n");
+        let _ = writeln!(out, "{} = {}", AUTOSAVE_TURNS, self.autosave_turns);
 
         let _ = writeln!(
             out,
@@ -513,6 +521,21 @@ impl Store for FileSystemStore {
             },
         }
 
+        match self.toml[AUTOSAVE_TURNS].as_integer() {
+            Some(autosave_turns) => {
+                if autosave_turns < 0 || autosave_turns > i64::from(i32::MAX) {
+                    log::error!("Settings error: `{}` must be 0 or more.", AUTOSAVE_TURNS);
+                } else {
+                    settings.autosave_turns = autosave_turns as i32;
+                }
+            }
+            None => log::info!(
+                "Settings: missing `{}`, falling back to: {}",
+                AUTOSAVE_TURNS,
+                settings.autosave_turns
+            ),
+        }
+
         match self.toml[FIRST_EVER_STARTUP].as_bool() {
             Some(first_ever_startup) => {
                 settings.first_ever_startup = first_ever_startup;
@@ -567,6 +590,8 @@ impl Store for FileSystemStore {
 
         self.toml[SOUND_VOLUME] = toml_edit::value(f64::from(settings.sound_volume));
 
+        self.toml[AUTOSAVE_TURNS] = toml_edit::value(i64::from(settings.autosave_turns));
+
         self.toml[FIRST_EVER_STARTUP] = toml_edit::value(settings.first_ever_startup);
 
         if let Err(err) = Self::write_settings_toml(&self.path, &self.toml) {
@@ -0,0 +1,181 @@
+use crate::{generators::Landscape, state::Challenge};
+
+use std::{error::Error, fmt, str::FromStr};
+
+/// Crockford's base32: no `I`, `L`, `O` or `U` so the codes are hard
+/// to misread when typed off a screenshot.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// The number of characters in a share code (without the dash).
+pub const LENGTH: usize = 10;
+
+/// Bump this whenever the meaning of the encoded bits changes. The
+/// old codes then stop parsing instead of silently starting a
+/// different game.
+const VERSION: u64 = 1;
+
+const SEED_BITS: u32 = 32;
+const CHALLENGE_BITS: u32 = 3;
+const LANDSCAPE_BITS: u32 = 2;
+const VERSION_BITS: u32 = 3;
+const PAYLOAD_BITS: u32 = SEED_BITS + CHALLENGE_BITS + LANDSCAPE_BITS + VERSION_BITS;
+const CHECKSUM_BITS: u32 = LENGTH as u32 * 5 - PAYLOAD_BITS;
+
+/// Everything needed to play the same game as somebody else: the
+/// world seed, the challenge flags and the landscape.
+///
+/// It's written as ten base32 characters (`XXXXX-XXXXX`) that include
+/// a checksum so a typo is reported rather than starting a different
+/// game.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShareCode {
+    pub seed: u32,
+    pub challenge: Challenge,
+    pub landscape: Landscape,
+}
+
+impl ShareCode {
+    /// The Daily Run: every player gets the same game on the same
+    /// (local) date. It ignores the local challenge settings so the
+    /// runs can be compared.
+    pub fn daily(date: chrono::NaiveDate) -> Self {
+        let text = format!("dose-response daily run {}", date.format("%Y-%m-%d"));
+        let hash = blake3::hash(text.as_bytes());
+        let mut seed = [0; 4];
+        seed.copy_from_slice(&hash.as_bytes()[..4]);
+        Self {
+            seed: u32::from_le_bytes(seed),
+            challenge: Challenge::default(),
+            landscape: Landscape::default(),
+        }
+    }
+
+    /// Parse what the player typed into a seed field. This is either
+    /// a full share code or just a number in which case the game
+    /// uses the passed `challenge` and `landscape`.
+    pub fn from_seed_text(
+        text: &str,
+        challenge: Challenge,
+        landscape: Landscape,
+    ) -> Result<Self, Box<dyn Error>> {
+        let text = text.trim();
+        if let Ok(seed) = text.parse::<u32>() {
+            // NOTE: a ten digit number could also be a share code.
+            // Prefer the share code, the checksum makes an accidental
+            // match very unlikely.
+            if let Ok(code) = text.parse() {
+                return Ok(code);
+            }
+            return Ok(Self {
+                seed,
+                challenge,
+                landscape,
+            });
+        }
+        text.parse()
+    }
+
+    fn payload(self) -> u64 {
+        let challenge = u64::from(self.challenge.hide_unseen_tiles)
+            | u64::from(self.challenge.fast_depression) << 1
+            | u64::from(self.challenge.one_chance) << 2;
+        let landscape = match self.landscape {
+            Landscape::Forest => 0,
+            Landscape::Urban => 1,
+        };
+        u64::from(self.seed)
+            | challenge << SEED_BITS
+            | landscape << (SEED_BITS + CHALLENGE_BITS)
+            | VERSION << (SEED_BITS + CHALLENGE_BITS + LANDSCAPE_BITS)
+    }
+}
+
+fn checksum(payload: u64) -> u64 {
+    // NOTE: Fibonacci hashing. Every payload bit affects the top
+    // bits we keep.
+    payload.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - CHECKSUM_BITS)
+}
+
+fn mask(bits: u32) -> u64 {
+    (1 << bits) - 1
+}
+
+impl fmt::Display for ShareCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let payload = self.payload();
+        let bits = payload | checksum(payload) << PAYLOAD_BITS;
+        for index in 0..LENGTH {
+            if index == LENGTH / 2 {
+                f.write_str("-")?;
+            }
+            let shift = (LENGTH - 1 - index) * 5;
+            let character = ALPHABET[((bits >> shift) & 0b11111) as usize];
+            write!(f, "{}", character as char)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ShareCode {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bits: u64 = 0;
+        let mut length = 0;
+        for character in s.chars() {
+            if character == '-' || character.is_whitespace() {
+                continue;
+            }
+            let character = match character.to_ascii_uppercase() {
+                'O' => '0',
+                'I' | 'L' => '1',
+                character => character,
+            };
+            let value = match ALPHABET.iter().position(|&c| c as char == character) {
+                Some(value) => value as u64,
+                None => throw!(&format!(
+                    "Invalid character `{}` in the share code.",
+                    character
+                )),
+            };
+            bits = bits << 5 | value;
+            length += 1;
+            if length > LENGTH {
+                break;
+            }
+        }
+        if length != LENGTH {
+            throw!(&format!(
+                "A share code must have {} characters, got {}.",
+                LENGTH, length
+            ));
+        }
+
+        let payload = bits & mask(PAYLOAD_BITS);
+        if bits >> PAYLOAD_BITS != checksum(payload) {
+            throw!("The share code is not valid. Is there a typo?");
+        }
+        let version = payload >> (SEED_BITS + CHALLENGE_BITS + LANDSCAPE_BITS);
+        if version != VERSION {
+            throw!(&format!(
+                "The share code is from a different version of the game ({}).",
+                version
+            ));
+        }
+        let challenge = (payload >> SEED_BITS) & mask(CHALLENGE_BITS);
+        let landscape = match (payload >> (SEED_BITS + CHALLENGE_BITS)) & mask(LANDSCAPE_BITS) {
+            0 => Landscape::Forest,
+            1 => Landscape::Urban,
+            _ => throw!("The share code has an unknown landscape."),
+        };
+        Ok(Self {
+            seed: (payload & mask(SEED_BITS)) as u32,
+            challenge: Challenge {
+                hide_unseen_tiles: challenge & 0b001 != 0,
+                fast_depression: challenge & 0b010 != 0,
+                one_chance: challenge & 0b100 != 0,
+            },
+            landscape,
+        })
+    }
+}
@@ -2,7 +2,9 @@ use crate::{
     animation::{self, AreaOfEffect, ScreenFade},
     color::Color,
     engine::Mouse,
+    error::{Desync, ReplayError},
     formula,
+    generators::Landscape,
     graphic::Graphic,
     keys::{Key, Keys},
     monster,
@@ -11,26 +13,26 @@ use crate::{
     player::Player,
     point::Point,
     random::Random,
+    save,
+    settings::Settings,
+    share_code::ShareCode,
     stats::Stats,
     timer::Timer,
-    util,
     window::Window,
     windows,
     world::{MonsterId, World},
 };
 
 use std::{
-    collections::VecDeque,
+    collections::{BTreeMap, HashMap, VecDeque},
     error::Error,
-    fs::File,
+    fmt,
+    fs::{self, File},
     io::{self, Write},
     path::PathBuf,
     time::Duration,
 };
 
-#[cfg(feature = "replay")]
-use std::fs;
-
 use serde::{Deserialize, Serialize};
 
 const CHUNK_SIZE: i32 = 32;
@@ -96,6 +98,234 @@ pub enum VerificationWrapper {
     Hash([u8; 32]),
 }
 
+impl VerificationWrapper {
+    /// Compare the recorded verification with the actual game state.
+    /// The lack of a verification is treated as an automatic pass.
+    pub fn desync(&self, actual: &Verification) -> Option<Desync> {
+        match self {
+            VerificationWrapper::Verification(expected) => {
+                expected.diff(actual).map(|diff| Desync::Verification {
+                    tick_id: actual.tick_id,
+                    diff,
+                })
+            }
+            VerificationWrapper::Hash(hash_bytes) => {
+                let expected_hash = blake3::Hash::from_bytes(*hash_bytes);
+                if expected_hash == actual.hash() {
+                    None
+                } else {
+                    Some(Desync::Hash {
+                        tick_id: actual.tick_id,
+                        expected_hash: expected_hash.to_hex().to_string(),
+                        actual: actual.clone(),
+                    })
+                }
+            }
+            VerificationWrapper::None => None,
+        }
+    }
+}
+
+/// Version of the replay file format. Bump it whenever the
+/// `ReplayHeader` changes in a way older builds can't read.
+///
+/// Version 1 is the legacy format where the header consists of the
+/// seed, program version and the git commit, each on its own line.
+/// Version 2 replays always hold the inputs, version 3 added the
+/// command stream, the landscape and the regions.
+pub const REPLAY_FORMAT_VERSION: u32 = 3;
+
+/// The display the game was played on. The mouse positions in the
+/// replay log are only meaningful with the same window, tile and
+/// text sizes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplaySettings {
+    pub window_size_px: Point,
+    pub tile_size: i32,
+    pub text_size: i32,
+}
+
+impl DisplaySettings {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            window_size_px: Point::new(settings.window_width as i32, settings.window_height as i32),
+            tile_size: settings.tile_size,
+            text_size: settings.text_size,
+        }
+    }
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self::from_settings(&Settings::default())
+    }
+}
+
+/// What follows the header of a replay file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ReplayStream {
+    /// The keys and mouse of every tick (`Input`).
+    #[default]
+    Inputs,
+    /// The commands the player carried out (`ReplayCommand`). Unlike
+    /// the inputs, these don't depend on the UI layout or the key
+    /// handling so they survive changes to either.
+    Commands,
+}
+
+/// The first line of a replay file. It holds everything needed to
+/// reproduce the recorded game so the replay doesn't depend on the
+/// settings of the machine that plays it back.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub replay_format_version: u32,
+    pub version: String,
+    pub commit: String,
+    pub seed: u32,
+    pub world_size: Point,
+    pub map_size: Point,
+    pub panel_width: i32,
+    pub challenge: Challenge,
+    pub palette: Palette,
+    pub display: DisplaySettings,
+    pub invincible: bool,
+    #[serde(default)]
+    pub stream: ReplayStream,
+    #[serde(default)]
+    pub landscape: Landscape,
+    /// The replays from before the `generators::Region` existed have
+    /// the same kind of chunks everywhere.
+    #[serde(default)]
+    pub regions: bool,
+}
+
+impl ReplayHeader {
+    /// Header for the replays recorded before the header was
+    /// versioned. These only stored the seed, version and commit so
+    /// we fall back to the default values for everything else.
+    fn legacy(seed: u32, version: String, commit: String) -> Self {
+        Self {
+            replay_format_version: 1,
+            version,
+            commit,
+            seed,
+            world_size: crate::WORLD_SIZE,
+            map_size: Point::from_i32(crate::DISPLAYED_MAP_SIZE),
+            panel_width: crate::PANEL_WIDTH,
+            challenge: Challenge::default(),
+            palette: Palette::classic(),
+            display: DisplaySettings::default(),
+            invincible: false,
+            stream: ReplayStream::Inputs,
+            landscape: Landscape::default(),
+            regions: false,
+        }
+    }
+}
+
+/// How often (in ticks) we take a snapshot of the `State` while
+/// playing a replay back. Seeking restores the closest earlier
+/// snapshot and re-simulates the rest so this is a trade-off between
+/// memory and how long a seek takes.
+pub const REPLAY_SNAPSHOT_INTERVAL: i32 = 300;
+
+/// The most snapshots a replay keeps. Past that, every other one is
+/// dropped. Seeking into the older parts of a long replay takes longer
+/// then, but the memory doesn't keep growing.
+pub const MAX_REPLAY_SNAPSHOTS: usize = 100;
+
+/// A replay being fast-forwarded to `target_tick`. Once it gets
+/// there, the replay gets paused or resumed based on `paused`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReplaySeek {
+    pub target_tick: i32,
+    pub paused: bool,
+}
+
+/// How fast a replay plays back. `Normal` shows one tick per frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ReplaySpeed {
+    Quarter,
+    Half,
+    #[default]
+    Normal,
+    Double,
+    Quadruple,
+    Octuple,
+}
+
+impl ReplaySpeed {
+    pub fn faster(self) -> Self {
+        use self::ReplaySpeed::*;
+        match self {
+            Quarter => Half,
+            Half => Normal,
+            Normal => Double,
+            Double => Quadruple,
+            Quadruple | Octuple => Octuple,
+        }
+    }
+
+    pub fn slower(self) -> Self {
+        use self::ReplaySpeed::*;
+        match self {
+            Quarter | Half => Quarter,
+            Normal => Half,
+            Double => Normal,
+            Quadruple => Double,
+            Octuple => Quadruple,
+        }
+    }
+
+    /// How many frames every tick stays on the screen for.
+    pub fn frames_per_tick(self) -> i32 {
+        use self::ReplaySpeed::*;
+        match self {
+            Quarter => 4,
+            Half => 2,
+            _ => 1,
+        }
+    }
+
+    /// How many ticks are simulated for every rendered frame.
+    pub fn ticks_per_frame(self) -> i32 {
+        use self::ReplaySpeed::*;
+        match self {
+            Double => 2,
+            Quadruple => 4,
+            Octuple => 8,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for ReplaySpeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::ReplaySpeed::*;
+        let speed = match self {
+            Quarter => "0.25x",
+            Half => "0.5x",
+            Normal => "1x",
+            Double => "2x",
+            Quadruple => "4x",
+            Octuple => "8x",
+        };
+        f.write_str(speed)
+    }
+}
+
+/// A single line of a command stream replay.
+///
+/// The verification is taken right after the command was carried
+/// out. Its `tick_id` holds the position of the command in the stream
+/// because the ticks depend on how fast the player was.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayCommand {
+    pub turn: i32,
+    pub command: Command,
+    pub verification: VerificationWrapper,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Input {
     pub keys: Vec<Key>,
@@ -169,6 +399,99 @@ impl Verification {
 
         hasher.finalize()
     }
+
+    /// Everything that's different between this (expected) and the
+    /// `actual` verification. `None` if they're the same.
+    pub fn diff(&self, actual: &Verification) -> Option<VerificationDiff> {
+        fn field<T: Copy + PartialEq>(expected: T, actual: T) -> Option<(T, T)> {
+            if expected == actual {
+                None
+            } else {
+                Some((expected, actual))
+            }
+        }
+
+        let mut diff = VerificationDiff {
+            tick_id: field(self.tick_id, actual.tick_id),
+            turn: field(self.turn, actual.turn),
+            chunk_count: field(self.chunk_count, actual.chunk_count),
+            player_pos: field(self.player_pos, actual.player_pos),
+            monster_count: field(self.monsters.len(), actual.monsters.len()),
+            monsters: vec![],
+        };
+
+        if self.monsters != actual.monsters {
+            let expected_monsters: HashMap<Point, (Point, monster::Kind)> = self
+                .monsters
+                .iter()
+                .map(|&(pos, chunk_pos, kind)| (pos, (chunk_pos, kind)))
+                .collect();
+            let actual_monsters: HashMap<Point, (Point, monster::Kind)> = actual
+                .monsters
+                .iter()
+                .map(|&(pos, chunk_pos, kind)| (pos, (chunk_pos, kind)))
+                .collect();
+
+            for &(pos, chunk_pos, kind) in &self.monsters {
+                let expected = Some((chunk_pos, kind));
+                let actual = actual_monsters.get(&pos).copied();
+                if expected != actual {
+                    diff.monsters.push(MonsterDiff {
+                        pos,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+
+            for &(pos, chunk_pos, kind) in &actual.monsters {
+                if !expected_monsters.contains_key(&pos) {
+                    diff.monsters.push(MonsterDiff {
+                        pos,
+                        expected: None,
+                        actual: Some((chunk_pos, kind)),
+                    });
+                }
+            }
+        }
+
+        if diff == VerificationDiff::default() {
+            None
+        } else {
+            Some(diff)
+        }
+    }
+}
+
+/// The differences between the expected and actual `Verification`.
+/// Each field holds the `(expected, actual)` values and it's only set
+/// if they differ.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct VerificationDiff {
+    pub tick_id: Option<(i32, i32)>,
+    pub turn: Option<(i32, i32)>,
+    pub chunk_count: Option<(usize, usize)>,
+    pub player_pos: Option<(Point, Point)>,
+    pub monster_count: Option<(usize, usize)>,
+    pub monsters: Vec<MonsterDiff>,
+}
+
+/// A monster that's either missing or different in one of the
+/// compared verifications. The values are the monster's chunk
+/// position and kind.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MonsterDiff {
+    pub pos: Point,
+    pub expected: Option<(Point, monster::Kind)>,
+    pub actual: Option<(Point, monster::Kind)>,
+}
+
+/// The actual state of the replayed game at a given tick alongside
+/// the verification the replay file recorded for it.
+#[derive(Clone, Debug)]
+pub struct ReplayTick {
+    pub actual: Verification,
+    pub recorded: VerificationWrapper,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -208,6 +531,18 @@ pub struct State {
     // pub verifications: HashMap<i32, Verification>,
     #[serde(skip_serializing, skip_deserializing, default = "empty_command_logger")]
     pub input_logger: Box<dyn Write>,
+    /// The commands of the command stream replay being played back.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub recorded_commands: Vec<ReplayCommand>,
+    /// How many commands the player has carried out so far.
+    pub commands_executed: i32,
+    /// Records the command stream. `input_logger` records the inputs.
+    #[serde(skip_serializing, skip_deserializing, default = "empty_command_logger")]
+    pub command_logger: Box<dyn Write>,
+    /// Whether a replay is being converted into a command stream
+    /// (`record_commands`). Rewinding would log the commands again.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub recording_commands: bool,
     pub side: Side,
     pub turn: i32,
     pub tick_id: i32,
@@ -223,6 +558,12 @@ pub struct State {
     pub stats: Stats,
     pub pos_timer: Timer,
     pub path_walking_timer: Timer,
+    /// The player is controlled by the `autoplay` bot rather than
+    /// the keyboard and mouse.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub autoplay: bool,
+    #[serde(skip_serializing, skip_deserializing, default = "autoplay_timer")]
+    pub autoplay_timer: Timer,
     pub paused: bool,
     pub old_screen_pos: Point,
     pub new_screen_pos: Point,
@@ -249,6 +590,24 @@ pub struct State {
     pub selected_endgame_window_action: Option<windows::endgame::Action>,
     #[serde(skip_serializing, skip_deserializing)]
     pub selected_sidebar_action: Option<windows::sidebar::Action>,
+    /// The saved games shown in the Load Game window. They're read
+    /// when the window opens.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub save_slots: Vec<save::Slot>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub selected_save_slot: usize,
+    /// What the player typed into the New Game window's seed field.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub seed_text: String,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub seed_error: Option<String>,
+    /// The slot this game was loaded from. Saving it again goes there.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub save_slot: Option<String>,
+    /// The autosave the main menu offers to recover after the
+    /// previous session crashed.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub recoverable_session: Option<save::Slot>,
     pub current_help_window: windows::help::Page,
     pub inventory_focused: bool,
     /// Used for help contents pagination: how much are we scrolling by
@@ -265,6 +624,36 @@ pub struct State {
 
     pub challenge: Challenge,
     pub palette: Palette,
+
+    /// The header of the replay that's being recorded or played back.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub replay_header: Option<ReplayHeader>,
+    /// The file the game is being recorded into.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub replay_path: Option<PathBuf>,
+
+    /// Serialised snapshots of the replayed game keyed by their `tick_id`.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub replay_snapshots: BTreeMap<i32, Vec<u8>>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub replay_seek: Option<ReplaySeek>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub replay_speed: ReplaySpeed,
+    /// How many frames the current tick has been on the screen for.
+    /// Only used when the replay is slowed down.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub replay_tick_frames: i32,
+
+    /// When set, the replay doesn't check the recorded verifications
+    /// and it doesn't crash when it runs out of inputs. It collects
+    /// the state at every tick instead so it can be compared later.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub replay_ticks: Option<Vec<ReplayTick>>,
+
+    /// Set when the replay can't continue. The game stops right
+    /// after.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub replay_error: Option<ReplayError>,
 }
 
 impl State {
@@ -283,6 +672,8 @@ impl State {
         exit_after: bool,
         debug: bool,
         challenge: Challenge,
+        landscape: Landscape,
+        regions: bool,
         palette: Palette,
     ) -> State {
         let world_centre = (0, 0).into();
@@ -311,7 +702,15 @@ impl State {
             player
         };
 
-        let world = World::new(seed, world_size.x, CHUNK_SIZE, player.info(), challenge);
+        let world = World::new(
+            seed,
+            world_size.x,
+            CHUNK_SIZE,
+            player.info(),
+            challenge,
+            landscape,
+            regions,
+        );
 
         // TODO: I think we'll want to create a Commands queue again here and then use that from everything
 
@@ -333,6 +732,10 @@ impl State {
             commands: VecDeque::new(),
             player_path: Path::default(),
             input_logger: Box::new(log_writer),
+            recorded_commands: vec![],
+            commands_executed: 0,
+            command_logger: empty_command_logger(),
+            recording_commands: false,
             side: Side::Player,
             turn: 0,
             tick_id: 0,
@@ -347,6 +750,8 @@ impl State {
             stats: Default::default(),
             pos_timer: Timer::new(Duration::from_millis(0)),
             path_walking_timer: Timer::new_elapsed(formula::PLAYER_PATH_WALKING_DELAY, 1.0),
+            autoplay: false,
+            autoplay_timer: autoplay_timer(),
             old_screen_pos: (0, 0).into(),
             new_screen_pos: (0, 0).into(),
             offset_px: Point::zero(),
@@ -367,6 +772,12 @@ impl State {
             selected_settings_position: None,
             selected_endgame_window_action: None,
             selected_sidebar_action: None,
+            save_slots: vec![],
+            selected_save_slot: 0,
+            seed_text: String::new(),
+            seed_error: None,
+            save_slot: None,
+            recoverable_session: None,
             current_help_window: windows::help::Page::DoseResponse,
             inventory_focused: false,
             keyboard_scroll_delta: [0.0, 0.0],
@@ -375,6 +786,14 @@ impl State {
 
             challenge,
             palette,
+            replay_header: None,
+            replay_path: None,
+            replay_snapshots: BTreeMap::new(),
+            replay_seek: None,
+            replay_speed: ReplaySpeed::Normal,
+            replay_tick_frames: 0,
+            replay_ticks: None,
+            replay_error: None,
         }
     }
 
@@ -384,12 +803,16 @@ impl State {
         panel_width: i32,
         exit_after: bool,
         debug: bool,
+        invincible: bool,
         replay_path: Option<PathBuf>,
+        stream: ReplayStream,
+        seed: u32,
         challenge: Challenge,
+        landscape: Landscape,
         palette: Palette,
+        display: DisplaySettings,
     ) -> State {
         let inputs = VecDeque::new();
-        let seed = util::random_seed();
 
         let replay_path = replay_path.and_then(|p| {
             if p.exists() {
@@ -404,10 +827,12 @@ impl State {
             }
         });
 
-        let mut writer: Box<dyn Write> = if let Some(replay_path) = replay_path {
+        let mut recorded_path = None;
+        let writer: Box<dyn Write> = if let Some(replay_path) = replay_path {
             match File::create_new(&replay_path) {
                 Ok(f) => {
                     log::info!("Recording the gameplay to '{}'", replay_path.display());
+                    recorded_path = Some(replay_path);
                     Box::new(f)
                 }
                 Err(err) => {
@@ -431,12 +856,11 @@ Reason: '{}'.",
             Box::new(io::sink())
         };
 
-        log_header(&mut writer, seed);
         let cheating = false;
         let replay = false;
-        let invincible = false;
         let replay_full_speed = false;
-        State::new(
+        let regions = true;
+        let mut state = State::new(
             world_size,
             map_size,
             panel_width,
@@ -450,24 +874,141 @@ Reason: '{}'.",
             exit_after,
             debug,
             challenge,
+            landscape,
+            regions,
             palette,
-        )
+        );
+
+        // NOTE: `State::new` can override the seed so we need to
+        // build the header from the final state.
+        let header = ReplayHeader {
+            replay_format_version: REPLAY_FORMAT_VERSION,
+            version: crate::metadata::VERSION.into(),
+            commit: crate::metadata::GIT_HASH.into(),
+            seed: state.seed,
+            world_size: state.world_size,
+            map_size: state.map_size,
+            panel_width: state.panel_width,
+            challenge: state.challenge,
+            palette: state.palette,
+            display,
+            invincible,
+            stream,
+            landscape,
+            regions,
+        };
+        let logger = match stream {
+            ReplayStream::Inputs => &mut state.input_logger,
+            ReplayStream::Commands => {
+                std::mem::swap(&mut state.input_logger, &mut state.command_logger);
+                &mut state.command_logger
+            }
+        };
+        log_header(logger, &header);
+        state.replay_header = Some(header);
+        state.replay_path = recorded_path;
+
+        state
+    }
+
+    /// Keep recording a loaded game into the replay at `path`.
+    ///
+    /// The replay must hold everything up to where the game was
+    /// saved. If it holds more (the game was recovered from an
+    /// autosave after a crash), that part is copied into a new
+    /// replay and the recording continues there. The original replay
+    /// stays as it is so the crash can still be reproduced.
+    pub fn resume_replay(&mut self, path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        let mut lines = contents.lines();
+        let header: ReplayHeader = match lines.next() {
+            Some(line) => serde_json::from_str(line)?,
+            None => throw!("The replay file is empty."),
+        };
+        let saved = match header.stream {
+            ReplayStream::Inputs => self.tick_id,
+            ReplayStream::Commands => self.commands_executed,
+        } as usize;
+        let recorded = lines.count();
+
+        let (path, file) = if recorded == saved {
+            let file = fs::OpenOptions::new().append(true).open(path)?;
+            log::info!("Continuing the replay '{}'", path.display());
+            (path.to_path_buf(), file)
+        } else if recorded > saved {
+            let new_path = match generate_replay_path() {
+                Some(new_path) => new_path,
+                None => throw!("Can't create a new replay."),
+            };
+            let mut file = File::create_new(&new_path)?;
+            for line in contents.lines().take(saved + 1) {
+                writeln!(file, "{}", line)?;
+            }
+            log::info!(
+                "The replay '{}' goes past the save, continuing its first {} entries in '{}'",
+                path.display(),
+                saved,
+                new_path.display()
+            );
+            (new_path, file)
+        } else {
+            throw!(&format!(
+                "The replay has {} entries, but the game was saved after {}.",
+                recorded, saved
+            ));
+        };
+
+        match header.stream {
+            ReplayStream::Inputs => self.input_logger = Box::new(file),
+            ReplayStream::Commands => self.command_logger = Box::new(file),
+        }
+        self.replay_header = Some(header);
+        self.replay_path = Some(path);
+        Ok(())
+    }
+
+    /// A game that's neither shown nor recorded. See
+    /// `session::GameSession`.
+    pub fn new_session(seed: u32, challenge: Challenge, landscape: Landscape) -> State {
+        let cheating = false;
+        let invincible = false;
+        let replay = false;
+        let replay_full_speed = false;
+        let exit_after = false;
+        let debug = false;
+        let regions = true;
+        let mut state = State::new(
+            crate::WORLD_SIZE,
+            Point::from_i32(crate::DISPLAYED_MAP_SIZE),
+            crate::PANEL_WIDTH,
+            VecDeque::new(),
+            io::sink(),
+            seed,
+            cheating,
+            invincible,
+            replay,
+            replay_full_speed,
+            exit_after,
+            debug,
+            challenge,
+            landscape,
+            regions,
+            Palette::classic(),
+        );
+        state.game_session = GameSession::InProgress;
+        state.generate_world();
+        state
     }
 
     #[cfg_attr(not(feature = "replay"), allow(dead_code, unused_variables))]
     pub fn replay_game(
-        world_size: Point,
-        map_size: Point,
-        panel_width: i32,
         replay_path: &std::path::Path,
         cheating: bool,
         invincible: bool,
         replay_full_speed: bool,
         exit_after: bool,
         debug: bool,
-        challenge: Challenge,
-        palette: Palette,
-    ) -> Result<State, Box<dyn Error>> {
+    ) -> Result<State, ReplayError> {
         #[cfg(feature = "replay")]
         {
             use flate2::read::GzDecoder;
@@ -481,8 +1022,12 @@ Reason: '{}'.",
             // a gzip. If that fails, we'll rewind the file position
             // and try again as a text file containing the rewind info
             // directly.
-            #[allow(clippy::type_complexity)]
-            let mut lines: Box<dyn Iterator<Item = Result<String, Box<dyn Error>>>> = {
+            let parse_error = |line: usize, message: &dyn std::fmt::Display| ReplayError::Parse {
+                line,
+                message: message.to_string(),
+            };
+
+            let mut lines: Box<dyn Iterator<Item = io::Result<String>>> = {
                 let mut d = GzDecoder::new(&file);
                 if d.read_to_string(&mut s).is_ok() {
                     log::info!("Trying to read the replay file as gzip-compressed");
@@ -490,56 +1035,97 @@ Reason: '{}'.",
                 } else {
                     log::info!("Trying reading the file directly as a text file");
                     file.rewind()?;
-                    Box::new(
-                        BufReader::new(&file)
-                            .lines()
-                            .map(|r| r.map_err(|e| Box::new(e) as Box<dyn Error>)),
-                    )
+                    Box::new(BufReader::new(&file).lines())
                 }
             };
 
-            let seed: u32 = match lines.next() {
-                Some(seed_str) => seed_str?.parse()?,
-                None => throw!("The replay file is empty."),
-            };
-
-            match lines.next() {
-                Some(version) => {
-                    let version = version?;
-                    if version != crate::metadata::VERSION {
-                        log::warn!(
-                            "The replay file's version is: {}, but the program is: {}",
-                            version,
-                            crate::metadata::VERSION
-                        );
+            let header = match lines.next() {
+                Some(first_line) => {
+                    let first_line = first_line?;
+                    if let Ok(seed) = first_line.parse::<u32>() {
+                        log::info!("Reading a legacy replay header");
+                        let version = match lines.next() {
+                            Some(version) => version?,
+                            None => return Err(parse_error(2, &"Missing the version.")),
+                        };
+                        let commit = match lines.next() {
+                            Some(commit) => commit?,
+                            None => return Err(parse_error(3, &"Missing the commit hash.")),
+                        };
+                        ReplayHeader::legacy(seed, version, commit)
+                    } else {
+                        // NOTE: check the format version before
+                        // deserialising the whole header. A newer
+                        // header may not parse at all.
+                        let header: serde_json::Value = serde_json::from_str(&first_line)
+                            .map_err(|err| parse_error(1, &err))?;
+                        let format_version = header["replay_format_version"].as_u64();
+                        match format_version {
+                            Some(format_version)
+                                if format_version <= u64::from(REPLAY_FORMAT_VERSION) => {}
+                            Some(format_version) => {
+                                return Err(ReplayError::UnsupportedVersion {
+                                    version: format_version,
+                                    supported: REPLAY_FORMAT_VERSION,
+                                })
+                            }
+                            None => {
+                                return Err(parse_error(
+                                    1,
+                                    &"The header is missing the format version.",
+                                ))
+                            }
+                        }
+                        serde_json::from_value(header).map_err(|err| parse_error(1, &err))?
                     }
                 }
-                None => throw!("The replay file is missing the version."),
+                None => return Err(parse_error(1, &"The replay file is empty.")),
             };
-
-            match lines.next() {
-                Some(commit) => {
-                    let commit = commit?;
-                    if commit != crate::metadata::GIT_HASH {
-                        log::warn!(
-                            "The replay file's commit is: {}, but the program is: {}.",
-                            commit,
-                            crate::metadata::GIT_HASH
-                        );
-                    }
-                }
-                None => throw!("The replay file is missing the commit hash."),
+            let header_line_count = if header.replay_format_version == 1 {
+                3
+            } else {
+                1
             };
 
-            for line in lines {
+            if header.version != crate::metadata::VERSION {
+                log::warn!(
+                    "The replay file's version is: {}, but the program is: {}",
+                    header.version,
+                    crate::metadata::VERSION
+                );
+            }
+
+            if header.commit != crate::metadata::GIT_HASH {
+                log::warn!(
+                    "The replay file's commit is: {}, but the program is: {}.",
+                    header.commit,
+                    crate::metadata::GIT_HASH
+                );
+            }
+
+            let mut recorded_commands = vec![];
+            for (index, line) in lines.enumerate() {
+                let line_number = header_line_count + index + 1;
                 let line = line?;
-                // Try parsing it as an `Input` first, otherwise it's a `Verification`
-                #[allow(clippy::expect_used)]
-                let input =
-                    serde_json::from_str::<Input>(&line).expect("Could not parse replay Input.");
-                assert!(input.tick_id > 0);
+                if header.stream == ReplayStream::Commands {
+                    let command = serde_json::from_str::<ReplayCommand>(&line)
+                        .map_err(|err| parse_error(line_number, &err))?;
+                    recorded_commands.push(command);
+                    continue;
+                }
+                let input = serde_json::from_str::<Input>(&line)
+                    .map_err(|err| parse_error(line_number, &err))?;
+                let expected_tick_id = inputs.len() as i32 + 1;
+                if input.tick_id != expected_tick_id {
+                    return Err(parse_error(
+                        line_number,
+                        &format!(
+                            "Expected input for tick {}, got: {}",
+                            expected_tick_id, input.tick_id
+                        ),
+                    ));
+                }
                 let index = input.tick_id as usize - 1;
-                assert_eq!(inputs.len(), index);
 
                 // log::warn!("Reading input {}", input.tick_id);
                 // log::warn!(
@@ -557,23 +1143,35 @@ Reason: '{}'.",
             }
 
             log::info!("Replaying game log: '{}'", replay_path.display());
+            log::info!(
+                "Replay format version: {}, stream: {:?}, challenge: {:?}, landscape: {}, display: {:?}",
+                header.replay_format_version,
+                header.stream,
+                header.challenge,
+                header.landscape,
+                header.display
+            );
             let replay = true;
             let mut state = State::new(
-                world_size,
-                map_size,
-                panel_width,
+                header.world_size,
+                header.map_size,
+                header.panel_width,
                 inputs,
                 Box::new(io::sink()),
-                seed,
+                header.seed,
                 cheating,
-                invincible,
+                invincible || header.invincible,
                 replay,
                 replay_full_speed,
                 exit_after,
                 debug,
-                challenge,
-                palette,
+                header.challenge,
+                header.landscape,
+                header.regions,
+                header.palette,
             );
+            state.recorded_commands = recorded_commands;
+            state.replay_header = Some(header);
             state.game_session = GameSession::InProgress;
             state.generate_world();
             Ok(state)
@@ -582,20 +1180,53 @@ Reason: '{}'.",
         #[cfg(not(feature = "replay"))]
         {
             let mut state = Self::new_game(
-                world_size,
-                map_size,
-                panel_width,
+                crate::WORLD_SIZE,
+                Point::from_i32(crate::DISPLAYED_MAP_SIZE),
+                crate::PANEL_WIDTH,
                 exit_after,
                 debug,
+                false,
                 None,
-                challenge,
-                palette,
+                ReplayStream::Inputs,
+                crate::util::random_seed(),
+                Challenge::default(),
+                Landscape::default(),
+                Palette::classic(),
+                DisplaySettings::default(),
             );
             state.generate_world();
             Ok(state)
         }
     }
 
+    /// Load the replay the way the headless tools play it back: no
+    /// cheats and stop once it's over.
+    pub fn load_replay_for_tools(replay_path: &std::path::Path) -> Result<State, ReplayError> {
+        let cheating = false;
+        let invincible = false;
+        let replay_full_speed = false;
+        let exit_after = true;
+        let debug = false;
+
+        State::replay_game(
+            replay_path,
+            cheating,
+            invincible,
+            replay_full_speed,
+            exit_after,
+            debug,
+        )
+    }
+
+    /// The code other players can use to play this same game.
+    pub fn share_code(&self) -> ShareCode {
+        ShareCode {
+            seed: self.seed,
+            challenge: self.challenge,
+            landscape: self.world.landscape(),
+        }
+    }
+
     pub fn generate_world(&mut self) {
         self.world = World::new(
             self.seed,
@@ -603,6 +1234,8 @@ Reason: '{}'.",
             CHUNK_SIZE,
             self.player.info(),
             self.challenge,
+            self.world.landscape(),
+            self.world.regions(),
         );
     }
 
@@ -631,53 +1264,162 @@ Reason: '{}'.",
         }
     }
 
-    pub fn save_to_file(&self) -> Result<(), Box<dyn Error>> {
-        // TODO: select the filename dynamically!
-        let filename = "SAVEDGAME.sav";
-        let version_data = bincode::serialize(crate::metadata::VERSION)?;
-        let commit_data = bincode::serialize(crate::metadata::GIT_HASH)?;
-        let state_data = bincode::serialize(self)?;
-
-        // TODO: this can be compressed nicely!
+    /// The verification logged with every command of a command
+    /// stream replay. See `ReplayCommand`.
+    pub fn command_verification(&self) -> Verification {
+        Verification {
+            tick_id: self.commands_executed,
+            ..self.verification()
+        }
+    }
 
-        let mut file = File::create(filename)?;
-        file.write_all(&version_data)?;
-        file.write_all(&commit_data)?;
-        file.write_all(&state_data)?;
-        file.flush()?;
+    /// What the replay that's being recorded or played back holds.
+    pub fn replay_stream(&self) -> ReplayStream {
+        self.replay_header
+            .as_ref()
+            .map_or(ReplayStream::Inputs, |header| header.stream)
+    }
 
+    /// Write the commands of the replay that's being played back into
+    /// a new command stream replay at `path`. This lets us convert
+    /// the old input replays.
+    pub fn record_commands(&mut self, path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        let mut header = match self.replay_header.clone() {
+            Some(header) => header,
+            None => throw!("There's no replay header to record the commands with."),
+        };
+        header.replay_format_version = REPLAY_FORMAT_VERSION;
+        header.version = crate::metadata::VERSION.into();
+        header.commit = crate::metadata::GIT_HASH.into();
+        header.stream = ReplayStream::Commands;
+        let mut writer = File::create_new(path)?;
+        log_header(&mut writer, &header);
+        log::info!("Recording the replay commands to '{}'", path.display());
+        self.command_logger = Box::new(writer);
+        self.recording_commands = true;
         Ok(())
     }
 
-    pub fn load_from_file() -> Result<State, Box<dyn Error>> {
-        let filename = "SAVEDGAME.sav";
-        let state = {
-            let file = File::open(filename)?;
-            let version: String = bincode::deserialize_from(&file)?;
-            log::info!("Savefile version {}", version);
-            if version != crate::metadata::VERSION {
-                log::warn!("The game was saved in a different version: {}. This release has version: {}. The game might not load properly.",
-                           version,
-                           crate::metadata::VERSION);
-            }
-            let commit: String = bincode::deserialize_from(&file)?;
-            log::info!("Savefile commit {}", commit);
-            if commit != crate::metadata::GIT_HASH {
-                log::warn!("The game was saved in a different commit: {}. This release has commit: {}. The game might not load properly.",
-                           commit,
-                crate::metadata::GIT_HASH);
-            }
-            bincode::deserialize_from(&file)?
+    /// Store a snapshot of the replayed game if there isn't one in
+    /// the last `REPLAY_SNAPSHOT_INTERVAL` ticks.
+    pub fn take_replay_snapshot_if_due(&mut self) {
+        let due = match self.replay_snapshots.range(..=self.tick_id).next_back() {
+            Some((&snapshot_tick, _)) => self.tick_id - snapshot_tick >= REPLAY_SNAPSHOT_INTERVAL,
+            None => true,
         };
+        // NOTE: the animations aren't serialised, but the monsters
+        // wait for the explosions to finish. Restoring a snapshot
+        // taken mid-explosion would make the replay diverge.
+        if due && self.explosion_animation.is_none() && self.extra_animations.is_empty() {
+            match bincode::serialize(self) {
+                Ok(snapshot) => {
+                    log::debug!("Taking a replay snapshot at tick {}", self.tick_id);
+                    self.replay_snapshots.insert(self.tick_id, snapshot);
+                    if self.replay_snapshots.len() > MAX_REPLAY_SNAPSHOTS {
+                        self.thin_out_replay_snapshots();
+                    }
+                }
+                Err(err) => {
+                    log::error!(
+                        "Could not take a replay snapshot at tick {}: {}",
+                        self.tick_id,
+                        err
+                    );
+                }
+            }
+        }
+    }
+
+    /// Drop every other replay snapshot. The first one stays so we
+    /// can always go back to the start and so does the latest one.
+    fn thin_out_replay_snapshots(&mut self) {
+        let dropped: Vec<i32> = self
+            .replay_snapshots
+            .keys()
+            .copied()
+            .skip(1)
+            .step_by(2)
+            .collect();
+        log::debug!("Dropping {} replay snapshots", dropped.len());
+        for tick_id in dropped {
+            self.replay_snapshots.remove(&tick_id);
+        }
+    }
 
-        if let Err(error) = ::std::fs::remove_file(filename) {
-            log::error!(
-                "Failed to delete the successfully loaded savegame. Error: {:?}",
-                error
+    /// Jump to the given tick of the replay.
+    ///
+    /// Going backwards (or far enough forward) restores the closest
+    /// earlier snapshot. The remaining ticks are then re-simulated by
+    /// `game::update` which skips rendering until it reaches the
+    /// target. The replay keeps its current paused state afterwards.
+    ///
+    /// A replay that's recording its commands can only go forward.
+    pub fn seek_replay(&mut self, target_tick: i32) -> Result<(), Box<dyn Error>> {
+        // NOTE: we don't know how many ticks a command stream takes
+        // until we play it.
+        let last_tick = match self.replay_stream() {
+            ReplayStream::Inputs => self.inputs.len() as i32,
+            ReplayStream::Commands => i32::MAX,
+        };
+        let target_tick = target_tick.clamp(0, last_tick);
+        if self.recording_commands && target_tick < self.tick_id {
+            throw!("Can't rewind the replay while recording its commands.");
+        }
+        let paused = match self.replay_seek {
+            Some(seek) => seek.paused,
+            None => self.paused,
+        };
+        let snapshot = self
+            .replay_snapshots
+            .range(..=target_tick)
+            .next_back()
+            .filter(|(&snapshot_tick, _)| {
+                target_tick < self.tick_id || snapshot_tick > self.tick_id
+            });
+
+        if let Some((&snapshot_tick, snapshot)) = snapshot {
+            log::info!(
+                "Seeking to tick {} from the snapshot at tick {}",
+                target_tick,
+                snapshot_tick
             );
+            let mut restored: State = bincode::deserialize(snapshot)?;
+            // NOTE: carry over everything that's not in the snapshot.
+            restored.inputs = std::mem::take(&mut self.inputs);
+            restored.input_logger =
+                std::mem::replace(&mut self.input_logger, empty_command_logger());
+            restored.recorded_commands = std::mem::take(&mut self.recorded_commands);
+            restored.command_logger =
+                std::mem::replace(&mut self.command_logger, empty_command_logger());
+            restored.recording_commands = self.recording_commands;
+            restored.stats = std::mem::take(&mut self.stats);
+            restored.replay_header = self.replay_header.take();
+            restored.replay_snapshots = std::mem::take(&mut self.replay_snapshots);
+            restored.replay_speed = self.replay_speed;
+            *self = restored;
+        } else if target_tick < self.tick_id {
+            throw!(&format!(
+                "There's no replay snapshot before tick {}.",
+                target_tick
+            ));
+        } else {
+            log::info!("Seeking to tick {}", target_tick);
         }
 
-        Ok(state)
+        self.paused = false;
+        self.replay_tick_frames = 0;
+        self.replay_seek = Some(ReplaySeek {
+            target_tick,
+            paused,
+        });
+        Ok(())
+    }
+
+    /// The replay is paused or it's slowed down and the current tick
+    /// hasn't been on the screen long enough yet. Either way, the
+    /// game doesn't advance this frame.
+    pub fn replay_paused(&self) -> bool {
+        self.paused || self.replay_tick_frames > 0
     }
 
     pub fn screen_left_top_corner(&self) -> Point {
@@ -725,10 +1467,20 @@ fn empty_command_logger() -> Box<dyn Write> {
     Box::new(io::sink())
 }
 
-pub fn log_header<W: Write>(writer: &mut W, seed: u32) {
-    let _ = writeln!(writer, "{}", seed);
-    let _ = writeln!(writer, "{}", crate::metadata::VERSION);
-    let _ = writeln!(writer, "{}", crate::metadata::GIT_HASH);
+/// The bot makes its first move right away.
+pub(crate) fn autoplay_timer() -> Timer {
+    Timer::new_elapsed(formula::AUTOPLAY_DELAY, 1.0)
+}
+
+pub fn log_header<W: Write>(writer: &mut W, header: &ReplayHeader) {
+    match serde_json::to_string(header) {
+        Ok(json_header) => {
+            let _ = writeln!(writer, "{}", json_header);
+        }
+        Err(err) => {
+            log::error!("Could not serialise the replay header to JSON: {}", err);
+        }
+    }
 }
 
 pub fn log_input<W: Write>(writer: &mut W, input: Input) {
@@ -741,3 +1493,60 @@ pub fn log_input<W: Write>(writer: &mut W, input: Input) {
         }
     }
 }
+
+/// Write a replay with the `header` and `inputs` into `path`.
+pub fn write_replay(
+    path: &std::path::Path,
+    header: &ReplayHeader,
+    inputs: &[Input],
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = io::BufWriter::new(File::create(path)?);
+    log_header(&mut writer, header);
+    for input in inputs {
+        log_input(&mut writer, input.clone());
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn log_command<W: Write>(writer: &mut W, command: &ReplayCommand) {
+    match serde_json::to_string(command) {
+        Ok(json_command) => {
+            let _ = writeln!(writer, "{}", json_command);
+        }
+        Err(err) => {
+            log::error!("Could not serialise {:?} to JSON: {}", command, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReplaySpeed::{self, *};
+
+    #[test]
+    fn replay_speeds() {
+        let speeds: [(ReplaySpeed, i32, i32); 6] = [
+            (Quarter, 4, 1),
+            (Half, 2, 1),
+            (Normal, 1, 1),
+            (Double, 1, 2),
+            (Quadruple, 1, 4),
+            (Octuple, 1, 8),
+        ];
+        for (speed, frames_per_tick, ticks_per_frame) in speeds {
+            assert_eq!(speed.frames_per_tick(), frames_per_tick, "{}", speed);
+            assert_eq!(speed.ticks_per_frame(), ticks_per_frame, "{}", speed);
+        }
+    }
+
+    #[test]
+    fn replay_speed_steps() {
+        assert_eq!(Quarter.slower(), Quarter);
+        assert_eq!(Quarter.faster(), Half);
+        assert_eq!(Normal.faster(), Double);
+        assert_eq!(Normal.slower(), Half);
+        assert_eq!(Octuple.faster(), Octuple);
+        assert_eq!(Octuple.slower(), Quadruple);
+    }
+}
@@ -20,6 +20,10 @@ pub enum Window {
         message: String,
         ttl: Option<Duration>,
     },
+    // NOTE: the window stack is in the save files. New windows go at
+    // the end so the old saves still load.
+    LoadGame,
+    NewGame,
 }
 
 pub fn message_box<S: Into<String>>(title: S, message: S) -> Window {
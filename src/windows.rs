@@ -3,8 +3,10 @@ use serde::{Deserialize, Serialize};
 pub mod call_to_action;
 pub mod endgame;
 pub mod help;
+pub mod load_game;
 pub mod main_menu;
 pub mod message;
+pub mod new_game;
 pub mod settings;
 pub mod sidebar;
 
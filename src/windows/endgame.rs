@@ -91,6 +91,7 @@ pub fn process(
         .show(ui.ctx(), |ui| {
             ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
                 ui.label(format!("Turns: {}", state.turn));
+                ui.label(format!("Share code: {}", state.share_code()));
                 ui.label("");
                 ui.label(format!(
                     "Longest High streak: {} turns",
@@ -196,7 +197,11 @@ pub fn process(
 
     match action {
         Some(Action::NewGame) => {
-            let mut new_state = Box::new(game::create_new_game_state(state, settings.challenge()));
+            let mut new_state = Box::new(if state.autoplay {
+                game::create_demo_game_state(state, settings)
+            } else {
+                game::create_new_game_state(state, settings)
+            });
             new_state.game_session = GameSession::InProgress;
             RunningState::NewGame(new_state)
         }
@@ -0,0 +1,136 @@
+use crate::{
+    audio::{Audio, Effect},
+    engine::Display,
+    game::RunningState,
+    gamepad::Gamepad,
+    keys::KeyCode,
+    save,
+    state::State,
+    ui,
+    window::{self, Window},
+};
+
+use std::time::Duration;
+
+use egui::{self, Ui};
+
+const SLOT_KEYS: [KeyCode; 9] = [
+    KeyCode::D1,
+    KeyCode::D2,
+    KeyCode::D3,
+    KeyCode::D4,
+    KeyCode::D5,
+    KeyCode::D6,
+    KeyCode::D7,
+    KeyCode::D8,
+    KeyCode::D9,
+];
+
+pub fn process(
+    state: &mut State,
+    ui: &mut Ui,
+    gamepad: &Gamepad,
+    display: &Display,
+    audio: &mut Audio,
+) -> RunningState {
+    let mut load = None;
+    let mut window_is_open = true;
+
+    let padding = 50.0;
+    let window_size = [
+        600.0_f32.min(display.screen_size_px.x as f32 - padding),
+        400.0_f32.min(display.screen_size_px.y as f32 - padding),
+    ];
+    let window_pos_px = [
+        (display.screen_size_px.x as f32 - window_size[0]) / 2.0,
+        (display.screen_size_px.y as f32 - window_size[1]) / 2.0,
+    ];
+
+    egui::Window::new("Load Game")
+        .open(&mut window_is_open)
+        .collapsible(false)
+        .fixed_pos(window_pos_px)
+        .fixed_size(window_size)
+        .show(ui.ctx(), |ui| {
+            if state.save_slots.is_empty() {
+                ui.label("There are no saved games.");
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (index, slot) in state.save_slots.iter().enumerate() {
+                    let metadata = &slot.metadata;
+                    let hotkey = if index < SLOT_KEYS.len() {
+                        format!("[{}]", index + 1)
+                    } else {
+                        "   ".into()
+                    };
+                    let text = format!(
+                        "{} {}  {}\n    Turn {}, Will {}, {}, seed {}",
+                        hotkey,
+                        metadata.name,
+                        metadata.date,
+                        metadata.turn,
+                        metadata.will,
+                        metadata.mind,
+                        metadata.seed,
+                    );
+                    let button = ui::button(ui, &text, true, &state.palette);
+                    if index == state.selected_save_slot {
+                        button.request_focus();
+                    }
+                    if button.clicked() {
+                        load = Some(index);
+                    }
+                }
+            });
+        });
+
+    let stick_flicked_up = gamepad.left_stick_flicked && gamepad.left_stick_y > 0.0;
+    let stick_flicked_down = gamepad.left_stick_flicked && gamepad.left_stick_y < 0.0;
+    let slot_count = state.save_slots.len();
+
+    if load.is_none() {
+        if let Some(index) = SLOT_KEYS
+            .iter()
+            .position(|&code| state.keys.matches_code(code))
+        {
+            load = Some(index);
+        } else if state.keys.matches_code(KeyCode::Enter) {
+            load = Some(state.selected_save_slot);
+        } else if slot_count > 0 && (state.keys.matches_code(KeyCode::Down) || stick_flicked_down) {
+            state.selected_save_slot = (state.selected_save_slot + 1) % slot_count;
+            audio.mix_sound_effect(Effect::Click, Duration::from_millis(0));
+        } else if slot_count > 0 && (state.keys.matches_code(KeyCode::Up) || stick_flicked_up) {
+            state.selected_save_slot = (state.selected_save_slot + slot_count - 1) % slot_count;
+            audio.mix_sound_effect(Effect::Click, Duration::from_millis(0));
+        } else if !window_is_open
+            || state.keys.matches_code(KeyCode::Esc)
+            || state.mouse.right_clicked
+        {
+            state.window_stack.pop();
+            return RunningState::Running;
+        }
+    }
+
+    if let Some(slot) = load.and_then(|index| state.save_slots.get(index).cloned()) {
+        audio.mix_sound_effect(Effect::Click, Duration::from_millis(0));
+        match save::load(&slot.path) {
+            Ok(new_state) => {
+                *state = new_state;
+                state.save_slot = Some(slot.metadata.name);
+                if state.window_stack.top() == Window::MainMenu {
+                    state.window_stack.pop();
+                }
+            }
+            Err(error) => {
+                log::error!("Error loading the game: {:?}", error);
+                state.window_stack.pop();
+                state.window_stack.push(window::message_box(
+                    "Load Game",
+                    "Error: could not load the game.",
+                ));
+            }
+        }
+    }
+
+    RunningState::Running
+}
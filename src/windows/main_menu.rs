@@ -5,7 +5,9 @@ use crate::{
     game::RunningState,
     gamepad::Gamepad,
     keys::KeyCode,
+    save,
     settings::Settings,
+    share_code::ShareCode,
     state::{GameSession, State},
     ui,
     window::{self, Window},
@@ -21,8 +23,11 @@ use std::time::Duration;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum MenuItem {
+    Recover,
     Resume,
     NewGame,
+    DailyRun,
+    Demo,
     Help,
     Settings,
     SaveAndQuit,
@@ -95,9 +100,12 @@ pub fn process(
         ui.label("");
 
         let game_in_progress = state.game_session == GameSession::InProgress;
+        let can_recover = state.recoverable_session.is_some();
 
         if state.selected_menu_action.is_none() {
-            state.selected_menu_action = if game_in_progress {
+            state.selected_menu_action = if can_recover {
+                Some(MenuItem::Recover)
+            } else if game_in_progress {
                 Some(MenuItem::Resume)
             } else {
                 Some(MenuItem::NewGame)
@@ -111,8 +119,17 @@ pub fn process(
             use MenuItem::*;
             // TODO: this is ignoring any disabled items, we need to handle those!
             let new_selected_action = match state.selected_menu_action {
+                Some(Recover) => {
+                    if game_in_progress {
+                        Resume
+                    } else {
+                        NewGame
+                    }
+                }
                 Some(Resume) => NewGame,
-                Some(NewGame) => Help,
+                Some(NewGame) => DailyRun,
+                Some(DailyRun) => Demo,
+                Some(Demo) => Help,
                 Some(Help) => Settings,
                 Some(Settings) => {
                     if game_in_progress {
@@ -124,7 +141,9 @@ pub fn process(
                 Some(SaveAndQuit) => Load,
                 Some(Load) => Quit,
                 Some(Quit) => {
-                    if game_in_progress {
+                    if can_recover {
+                        Recover
+                    } else if game_in_progress {
                         Resume
                     } else {
                         NewGame
@@ -147,15 +166,26 @@ pub fn process(
             use MenuItem::*;
             // TODO: this is ignoring any disabled items, we need to handle those!
             let new_selected_action = match state.selected_menu_action {
-                Some(Resume) => Quit,
+                Some(Recover) => Quit,
+                Some(Resume) => {
+                    if can_recover {
+                        Recover
+                    } else {
+                        Quit
+                    }
+                }
                 Some(NewGame) => {
                     if game_in_progress {
                         Resume
+                    } else if can_recover {
+                        Recover
                     } else {
                         Quit
                     }
                 }
-                Some(Help) => NewGame,
+                Some(DailyRun) => NewGame,
+                Some(Demo) => DailyRun,
+                Some(Help) => Demo,
                 Some(Settings) => Help,
                 Some(SaveAndQuit) => Settings,
                 Some(Load) => {
@@ -181,6 +211,16 @@ pub fn process(
 
         // TODO: handle the "gamepad up" button too!
 
+        if can_recover {
+            let resp = ui::button(ui, "Re[c]over last session", active, &state.palette);
+            if active && state.selected_menu_action == Some(MenuItem::Recover) {
+                resp.request_focus();
+            }
+            if resp.clicked() {
+                action = Some(MenuItem::Recover);
+            }
+        }
+
         if game_in_progress {
             let resp = ui::button(ui, "[R]esume", active, &state.palette);
             if active && state.selected_menu_action == Some(MenuItem::Resume) {
@@ -201,6 +241,26 @@ pub fn process(
             }
         }
 
+        {
+            let resp = ui::button(ui, "Dail[y] Run", active, &state.palette);
+            if active && state.selected_menu_action == Some(MenuItem::DailyRun) {
+                resp.request_focus();
+            }
+            if resp.clicked() {
+                action = Some(MenuItem::DailyRun);
+            }
+        }
+
+        {
+            let resp = ui::button(ui, "[D]emo", active, &state.palette);
+            if active && state.selected_menu_action == Some(MenuItem::Demo) {
+                resp.request_focus();
+            }
+            if resp.clicked() {
+                action = Some(MenuItem::Demo);
+            }
+        }
+
         {
             let resp = ui::button(ui, "[H]elp", active, &state.palette);
             if active && state.selected_menu_action == Some(MenuItem::Help) {
@@ -262,11 +322,16 @@ pub fn process(
         }
 
         ui.label("");
+        if state.game_session.started() {
+            ui.label(format!("Share code: {}", state.share_code()));
+        }
         ui.label("\"You cannot lose if you do not play.\"\n-- Marla Daniels");
     });
 
     if action.is_none() && active {
-        if state.keys.matches_code(KeyCode::E) {
+        if state.keys.matches_code(KeyCode::C) && state.recoverable_session.is_some() {
+            action = Some(MenuItem::Recover);
+        } else if state.keys.matches_code(KeyCode::E) {
             action = Some(MenuItem::Settings);
         } else if state.keys.matches_code(KeyCode::H)
             || state.keys.matches_code(KeyCode::QuestionMark)
@@ -276,6 +341,10 @@ pub fn process(
             action = Some(MenuItem::Load);
         } else if state.keys.matches_code(KeyCode::N) {
             action = Some(MenuItem::NewGame);
+        } else if state.keys.matches_code(KeyCode::Y) {
+            action = Some(MenuItem::DailyRun);
+        } else if state.keys.matches_code(KeyCode::D) {
+            action = Some(MenuItem::Demo);
         } else if state.keys.matches_code(KeyCode::Q) {
             action = Some(MenuItem::Quit);
         } else if state.keys.matches_code(KeyCode::R)
@@ -293,6 +362,28 @@ pub fn process(
     if let Some(action) = action {
         audio.mix_sound_effect(Effect::Click, Duration::from_millis(0));
         match action {
+            MenuItem::Recover => {
+                if let Some(slot) = state.recoverable_session.take() {
+                    match save::load(&slot.path) {
+                        Ok(new_state) => {
+                            *state = new_state;
+                            if state.window_stack.top() == Window::MainMenu {
+                                state.window_stack.pop();
+                            }
+                        }
+                        Err(error) => {
+                            log::error!("Error recovering the last session: {:?}", error);
+                            state.selected_menu_action = None;
+                            state.window_stack.push(window::message_box(
+                                "Recover Last Session",
+                                "Error: could not recover the last session.",
+                            ));
+                        }
+                    }
+                }
+                return RunningState::Running;
+            }
+
             MenuItem::Resume => {
                 if state.game_session.started() {
                     state.window_stack.pop();
@@ -301,17 +392,23 @@ pub fn process(
             }
 
             MenuItem::NewGame => {
-                // NOTE: don't create a new state if we've just
-                // started the game. It's got a seend and world
-                // already generated so there's no need to throw it
-                // away and start a new one.
-                if state.game_session == GameSession::NotStarted {
-                    state.window_stack.pop();
-                    state.game_session = GameSession::InProgress;
-                    return RunningState::Running;
-                }
-                let mut new_state =
-                    Box::new(game::create_new_game_state(state, settings.challenge()));
+                state.seed_text.clear();
+                state.seed_error = None;
+                state.window_stack.push(Window::NewGame);
+                return RunningState::Running;
+            }
+
+            MenuItem::DailyRun => {
+                let today = chrono::Local::now().date_naive();
+                let code = ShareCode::daily(today);
+                log::info!("Starting the Daily Run for {} with: {}", today, code);
+                let mut new_state = Box::new(game::create_shared_game_state(state, settings, code));
+                new_state.game_session = GameSession::InProgress;
+                return RunningState::NewGame(new_state);
+            }
+
+            MenuItem::Demo => {
+                let mut new_state = Box::new(game::create_demo_game_state(state, settings));
                 new_state.game_session = GameSession::InProgress;
                 return RunningState::NewGame(new_state);
             }
@@ -328,8 +425,13 @@ pub fn process(
 
             MenuItem::SaveAndQuit => {
                 if state.game_session == GameSession::InProgress {
-                    match state.save_to_file() {
-                        Ok(()) => return RunningState::Stopped,
+                    let slots = save::Slots::in_data_dir();
+                    let name = state
+                        .save_slot
+                        .clone()
+                        .unwrap_or_else(|| slots.next_free_name());
+                    match slots.save(&name, state) {
+                        Ok(_path) => return RunningState::Stopped,
                         Err(error) => {
                             // NOTE: we couldn't save the game so we'll keep going
                             log::error!("Error saving the game: {:?}", error);
@@ -343,23 +445,20 @@ pub fn process(
                 return RunningState::Running;
             }
 
-            MenuItem::Load => match State::load_from_file() {
-                Ok(new_state) => {
-                    *state = new_state;
-                    if state.window_stack.top() == Window::MainMenu {
-                        state.window_stack.pop();
-                    }
-                    return RunningState::Running;
-                }
-                Err(error) => {
-                    log::error!("Error loading the game: {:?}", error);
-                    state.window_stack.push(window::message_box(
-                        "Load Game",
-                        "Error: could not load the game.",
-                    ));
-                    return RunningState::Running;
-                }
-            },
+            MenuItem::Load => {
+                let slots = save::Slots::in_data_dir();
+                state.save_slots = slots.list().unwrap_or_else(|error| {
+                    log::error!(
+                        "Error listing the saved games in '{}': {:?}",
+                        slots.dir().display(),
+                        error
+                    );
+                    vec![]
+                });
+                state.selected_save_slot = 0;
+                state.window_stack.push(Window::LoadGame);
+                return RunningState::Running;
+            }
 
             MenuItem::Quit => {
                 return RunningState::Stopped;
@@ -0,0 +1,195 @@
+use crate::{
+    audio::{Audio, Effect},
+    engine::Display,
+    game::{self, RunningState},
+    keys::{Key, KeyCode},
+    settings::Settings,
+    share_code::ShareCode,
+    state::{GameSession, State},
+    ui,
+    window::Window,
+};
+
+use std::time::Duration;
+
+use egui::{self, Ui};
+
+/// Long enough for a share code with a dash and a few stray spaces.
+const MAX_SEED_TEXT_LENGTH: usize = 16;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Action {
+    Start,
+    Back,
+}
+
+pub fn process(
+    state: &mut State,
+    ui: &mut Ui,
+    settings: &Settings,
+    display: &Display,
+    audio: &mut Audio,
+) -> RunningState {
+    let mut action = None;
+    let mut window_is_open = true;
+
+    let padding = 50.0;
+    let window_size = [
+        600.0_f32.min(display.screen_size_px.x as f32 - padding),
+        300.0_f32.min(display.screen_size_px.y as f32 - padding),
+    ];
+    let window_pos_px = [
+        (display.screen_size_px.x as f32 - window_size[0]) / 2.0,
+        (display.screen_size_px.y as f32 - window_size[1]) / 2.0,
+    ];
+
+    egui::Window::new("New Game")
+        .open(&mut window_is_open)
+        .collapsible(false)
+        .fixed_pos(window_pos_px)
+        .fixed_size(window_size)
+        .show(ui.ctx(), |ui| {
+            ui.label("Type a seed or a share code to play the same game as somebody else.");
+            ui.label("Leave it empty for a random game.");
+            ui.label("");
+            ui.label(format!("Seed: {}_", state.seed_text));
+            if let Some(error) = &state.seed_error {
+                ui.label(error);
+            }
+            ui.label("");
+            ui.separator();
+            ui.columns(2, |c| {
+                c[0].with_layout(egui::Layout::top_down(egui::Align::Min), |ui| {
+                    if ui::button(ui, "[Enter] Start", true, &state.palette).clicked() {
+                        action = Some(Action::Start);
+                    }
+                });
+                c[1].with_layout(egui::Layout::top_down(egui::Align::Max), |ui| {
+                    if ui::button(ui, "[Esc] Back", true, &state.palette).clicked() {
+                        action = Some(Action::Back);
+                    }
+                });
+            });
+        });
+
+    if !window_is_open || state.mouse.right_clicked {
+        action = Some(Action::Back);
+    }
+
+    // NOTE: this window takes all the keys because every letter and
+    // digit goes into the seed field.
+    while let Some(key) = state.keys.get() {
+        if action.is_some() {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => action = Some(Action::Start),
+            KeyCode::Esc => action = Some(Action::Back),
+            KeyCode::Backspace => {
+                state.seed_text.pop();
+                state.seed_error = None;
+            }
+            _ => {
+                if let Some(character) = seed_character(key) {
+                    if state.seed_text.len() < MAX_SEED_TEXT_LENGTH {
+                        state.seed_text.push(character);
+                        state.seed_error = None;
+                    }
+                }
+            }
+        }
+    }
+
+    match action {
+        Some(Action::Back) => {
+            audio.mix_sound_effect(Effect::Click, Duration::from_millis(0));
+            state.window_stack.pop();
+        }
+        Some(Action::Start) => {
+            audio.mix_sound_effect(Effect::Click, Duration::from_millis(0));
+            let text = state.seed_text.trim();
+            if text.is_empty() {
+                // NOTE: don't create a new state if we've just
+                // started the game. It's got a seed and world
+                // already generated so there's no need to throw it
+                // away and start a new one.
+                if state.game_session == GameSession::NotStarted {
+                    while state.window_stack.top() != Window::Game {
+                        state.window_stack.pop();
+                    }
+                    state.game_session = GameSession::InProgress;
+                    return RunningState::Running;
+                }
+                let mut new_state = Box::new(game::create_new_game_state(state, settings));
+                new_state.game_session = GameSession::InProgress;
+                return RunningState::NewGame(new_state);
+            }
+
+            match ShareCode::from_seed_text(text, settings.challenge(), state.world.landscape()) {
+                Ok(code) => {
+                    log::info!("Starting a new game with the share code: {}", code);
+                    let mut new_state =
+                        Box::new(game::create_shared_game_state(state, settings, code));
+                    new_state.game_session = GameSession::InProgress;
+                    return RunningState::NewGame(new_state);
+                }
+                Err(error) => {
+                    log::warn!("Invalid seed `{}`: {}", text, error);
+                    state.seed_error = Some("That's not a valid seed or share code.".into());
+                }
+            }
+        }
+        None => {}
+    }
+
+    RunningState::Running
+}
+
+/// The character a key types into the seed field.
+fn seed_character(key: Key) -> Option<char> {
+    use crate::keys::KeyCode::*;
+    if key.ctrl || key.alt || key.logo {
+        return None;
+    }
+    let character = match key.code {
+        D0 | NumPad0 => '0',
+        D1 | NumPad1 => '1',
+        D2 | NumPad2 => '2',
+        D3 | NumPad3 => '3',
+        D4 | NumPad4 => '4',
+        D5 | NumPad5 => '5',
+        D6 | NumPad6 => '6',
+        D7 | NumPad7 => '7',
+        D8 | NumPad8 => '8',
+        D9 | NumPad9 => '9',
+        A => 'A',
+        B => 'B',
+        C => 'C',
+        D => 'D',
+        E => 'E',
+        F => 'F',
+        G => 'G',
+        H => 'H',
+        I => 'I',
+        J => 'J',
+        K => 'K',
+        L => 'L',
+        M => 'M',
+        N => 'N',
+        O => 'O',
+        P => 'P',
+        Q => 'Q',
+        R => 'R',
+        S => 'S',
+        T => 'T',
+        U => 'U',
+        V => 'V',
+        W => 'W',
+        X => 'X',
+        Y => 'Y',
+        Z => 'Z',
+        Space => ' ',
+        _ => return None,
+    };
+    Some(character)
+}
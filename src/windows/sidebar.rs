@@ -8,7 +8,7 @@ use crate::{
     player::Mind,
     point::Point,
     settings::Settings,
-    state::State,
+    state::{ReplayStream, State},
     ui,
 };
 
@@ -327,6 +327,30 @@ pub fn process(
         help_rect = help_response.rect;
     });
 
+    if state.replay {
+        ui.label("\nReplay:");
+        match state.replay_stream() {
+            ReplayStream::Inputs => {
+                ui.label(format!("Tick {} of {}", state.tick_id, state.inputs.len()));
+            }
+            ReplayStream::Commands => {
+                ui.label(format!("Tick {}", state.tick_id));
+                ui.label(format!(
+                    "Command {} of {}",
+                    state.commands_executed,
+                    state.recorded_commands.len()
+                ));
+            }
+        }
+        ui.label(format!("[Up/Down] Speed: {}", state.replay_speed));
+        if state.paused {
+            ui.label("[Space] Resume");
+        } else {
+            ui.label("[Space] Pause");
+        }
+        ui.label("[Left/Right] Step");
+    }
+
     if state.cheating {
         ui.label("CHEATING");
 
@@ -1,7 +1,7 @@
 use crate::{
     blocker::Blocker,
     formula,
-    generators::{self, GeneratedWorld},
+    generators::{GeneratedWorld, Landscape, Region, WorldGenerator},
     item::Item,
     level::{self, Cell, Level},
     monster::Monster,
@@ -10,6 +10,7 @@ use crate::{
     random::Random,
     ranged_int::InclusiveRange,
     rect::Rectangle,
+    save,
     state::Challenge,
 };
 
@@ -47,6 +48,8 @@ impl Chunk {
         size: i32,
         player_position: Point,
         challenge: Challenge,
+        generator: &dyn WorldGenerator,
+        regions: bool,
     ) -> Self {
         use std::num::Wrapping;
         let pos = position.position;
@@ -67,13 +70,21 @@ impl Chunk {
             monsters: vec![],
         };
 
+        let region = if regions {
+            let size = chunk.level.size();
+            Region::new(world_seed, pos + (size.x / 2, size.y / 2))
+        } else {
+            Region::default()
+        };
+
         let mut throwaway_rng = chunk.rng.clone();
-        let generated_data = generators::forrest::generate(
+        let generated_data = generator.generate(
             &mut chunk.rng,
             &mut throwaway_rng,
             chunk.level.size(),
             player_position,
             challenge,
+            region,
         );
 
         chunk.populate(generated_data);
@@ -160,7 +171,7 @@ impl<'a> Iterator for ChunkCells<'a> {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
-struct ChunkPosition {
+pub struct ChunkPosition {
     position: Point,
 }
 
@@ -169,6 +180,10 @@ pub struct World {
     seed: u32,
     max_half_size: i32,
     chunk_size: i32,
+    landscape: Landscape,
+    /// Whether the chunks change with their `Region`. The worlds
+    /// from before the regions existed are the same everywhere.
+    regions: bool,
     chunks: HashMap<ChunkPosition, Chunk>,
     challenge: Challenge,
 }
@@ -180,8 +195,10 @@ impl World {
         chunk_size: i32,
         player_info: PlayerInfo,
         challenge: Challenge,
+        landscape: Landscape,
+        regions: bool,
     ) -> World {
-        log::info!("World::new(seed: {seed}, dimension: {dimension}, chunk_size: {chunk_size}, player_info: {:?}, challenge: {:?})", player_info, challenge);
+        log::info!("World::new(seed: {seed}, dimension: {dimension}, chunk_size: {chunk_size}, player_info: {:?}, challenge: {:?}, landscape: {landscape}, regions: {regions})", player_info, challenge);
         assert!(dimension > 0);
         assert!(chunk_size > 0);
         assert_eq!(dimension % 2, 0);
@@ -191,6 +208,8 @@ impl World {
             seed,
             max_half_size: dimension / 2,
             chunk_size,
+            landscape,
+            regions,
             chunks: HashMap::new(),
             challenge,
         };
@@ -353,6 +372,15 @@ impl World {
             }
         }
     }
+
+    pub fn landscape(&self) -> Landscape {
+        self.landscape
+    }
+
+    pub fn regions(&self) -> bool {
+        self.regions
+    }
+
     /// Return the `ChunkPosition` for a given point within the chunk.
     ///
     /// Chunks have equal width and height and can have negative
@@ -398,10 +426,20 @@ impl World {
         let seed = self.seed;
         let chunk_size = self.chunk_size;
         let challenge = self.challenge;
+        let generator = self.landscape.generator();
+        let regions = self.regions;
         // TODO: figure out how to generate the starting chunks so the
         // player has some doses and food and no monsters.
         self.chunks.entry(chunk_position).or_insert_with(|| {
-            Chunk::new(seed, chunk_position, chunk_size, (0, 0).into(), challenge)
+            Chunk::new(
+                seed,
+                chunk_position,
+                chunk_size,
+                (0, 0).into(),
+                challenge,
+                generator,
+                regions,
+            )
         });
     }
 
@@ -782,3 +820,19 @@ impl World {
             .collect()
     }
 }
+
+/// All the worlds were forests without regions before the
+/// landscapes.
+impl From<save::v1::World> for World {
+    fn from(world: save::v1::World) -> Self {
+        World {
+            seed: world.seed,
+            max_half_size: world.max_half_size,
+            chunk_size: world.chunk_size,
+            landscape: Landscape::Forest,
+            regions: false,
+            chunks: world.chunks,
+            challenge: world.challenge,
+        }
+    }
+}
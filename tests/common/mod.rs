@@ -0,0 +1,33 @@
+//! Helpers shared by the integration tests.
+//!
+//! NOTE: a failing `expect` is exactly what we want a test to do. The
+//! test crates allow `clippy::expect_used` for that.
+
+// NOTE: every test crate compiles its own copy of this module and
+// none of them uses all of it.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+/// A directory for the test's files. It's deleted when the test ends
+/// whether it passes or not.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("dose-response-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -1,78 +1,326 @@
-use dose_response::settings::Store;
-
-use std::path::Path;
-
-fn test_replay(replay_path: &Path) {
-    assert!(replay_path.exists());
-
-    let cheating = false;
-    let invincible = false;
-    let replay_full_speed = false;
-    let exit_after = true;
-    let debug = false;
-
-    let settings_store = dose_response::settings::FileSystemStore::new();
-    let settings = settings_store.load();
-    let challenge = settings.challenge();
-    let palette = settings.palette();
-
-    let state = dose_response::state::State::replay_game(
-        dose_response::WORLD_SIZE,
-        dose_response::point::Point::from_i32(dose_response::DISPLAYED_MAP_SIZE),
-        dose_response::PANEL_WIDTH,
-        replay_path,
-        cheating,
-        invincible,
-        replay_full_speed,
-        exit_after,
-        debug,
-        challenge,
-        palette,
-    )
-    .expect("state created");
-
-    let result = dose_response::engine::headless::main_loop(settings_store, Box::new(state));
-    assert!(matches!(result, Ok(())));
+#![allow(clippy::expect_used)]
+
+mod common;
+
+use common::TempDir;
+use dose_response::{
+    engine::headless,
+    error::{Desync, ReplayError},
+    player::CauseOfDeath,
+    replay_diff,
+    replay_minimise::{self, Failure},
+    report::Report,
+    settings::NoOpStore,
+    state::State,
+};
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+fn play_replay(replay_path: &Path) -> Result<(), ReplayError> {
+    // NOTE: Everything the replay needs comes from its header. The
+    // settings store only provides defaults for the audio and such so
+    // the developer's `settings.toml` can't influence the result.
+    let settings_store = NoOpStore;
+
+    let state = State::load_replay_for_tools(replay_path)?;
+    headless::main_loop(settings_store, Box::new(state), None)
+}
+
+/// Set this environment variable to write the expectations of the
+/// golden replays instead of checking them.
+const BLESS_VAR: &str = "DOSE_RESPONSE_BLESS";
+
+/// How a golden replay must end. It's stored next to the replay in
+/// `<name>.expected.json`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Expectation {
+    victory: bool,
+    cause_of_death: Option<CauseOfDeath>,
+    turns: i32,
+    /// Hash of the `Verification` of the final game state.
+    verification_hash: String,
+}
+
+impl Expectation {
+    fn new(state: &State) -> Self {
+        let report = Report::new(state);
+        Self {
+            victory: report.victory,
+            cause_of_death: report.cause_of_death,
+            turns: report.turns,
+            verification_hash: state.verification().hash().to_hex().to_string(),
+        }
+    }
+}
+
+fn is_expectation(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(".expected.json")
+}
+
+fn check_golden_replay(replay_path: &Path, bless: bool) -> Result<(), String> {
+    let expectation_path = replay_path.with_extension("expected.json");
+    let state = State::load_replay_for_tools(replay_path).map_err(|err| err.to_string())?;
+    let final_state =
+        headless::run(NoOpStore, Box::new(state), None).map_err(|err| err.to_string())?;
+    let actual = Expectation::new(&final_state);
+
+    if bless {
+        let json = serde_json::to_string_pretty(&actual).map_err(|err| err.to_string())?;
+        return std::fs::write(&expectation_path, json + "\n").map_err(|err| err.to_string());
+    }
+
+    let contents = std::fs::read_to_string(&expectation_path).map_err(|err| {
+        format!(
+            "Could not read {}: {}. Run the tests with `{}=1` to create it.",
+            expectation_path.display(),
+            err,
+            BLESS_VAR
+        )
+    })?;
+    let expected: Expectation = serde_json::from_str(&contents).map_err(|err| err.to_string())?;
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("expected: {:?}, actual: {:?}", expected, actual))
+    }
+}
+
+/// Write a modified copy of the replay as an uncompressed text file
+/// into `dir`.
+fn tampered_replay(
+    replay_path: &Path,
+    dir: &Path,
+    tamper: impl FnOnce(&mut Vec<String>),
+) -> PathBuf {
+    let mut contents = String::new();
+    flate2::read::GzDecoder::new(std::fs::File::open(replay_path).expect("replay opened"))
+        .read_to_string(&mut contents)
+        .expect("replay decompressed");
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+    tamper(&mut lines);
+
+    std::fs::create_dir_all(dir).expect("replay directory created");
+    let path = dir.join("replay");
+    std::fs::write(&path, lines.join("\n")).expect("tampered replay written");
+    path
+}
+
+/// Replace the verification recorded for tick 8 with a hash that
+/// can't match.
+fn break_verification_of_tick_8(lines: &mut [String]) {
+    let mut input: serde_json::Value = serde_json::from_str(&lines[10]).expect("input parsed");
+    input["verification"]["Hash"] = serde_json::json!([0_u8; 32].to_vec());
+    lines[10] = input.to_string();
 }
 
+/// Play every replay in `e2e-tests` and check it ends the way its
+/// expectation says.
 #[test]
-fn test_almost_replay() {
-    let replay_path = &Path::new("e2e-tests/almost-2024-09-27.gz");
-    test_replay(replay_path);
+fn test_golden_replays() {
+    let bless = std::env::var_os(BLESS_VAR).is_some();
+    let mut replay_paths: Vec<PathBuf> = std::fs::read_dir("e2e-tests")
+        .expect("e2e-tests listed")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && !is_expectation(path))
+        .collect();
+    replay_paths.sort();
+    assert!(!replay_paths.is_empty());
+
+    // NOTE: every replay takes a while so we play them all at once.
+    let failures: Vec<String> = std::thread::scope(|scope| {
+        let handles: Vec<_> = replay_paths
+            .iter()
+            .map(|path| (path, scope.spawn(move || check_golden_replay(path, bless))))
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|(path, handle)| {
+                let result = handle
+                    .join()
+                    .unwrap_or_else(|_| Err(String::from("The replay panicked.")));
+                result
+                    .err()
+                    .map(|failure| format!("{}: {}", path.display(), failure))
+            })
+            .collect()
+    });
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+// NOTE: the almost replay has the legacy three-line header so the
+// input for tick 8 is on line 11.
+
+/// Seek the replay to `tick_id` and play it there.
+fn seek(mut state: Box<State>, tick_id: i32) -> Box<State> {
+    state.paused = true;
+    state.seek_replay(tick_id).expect("replay seeked");
+    let state = headless::run(NoOpStore, state, None).expect("replay played");
+    assert_eq!(state.tick_id, tick_id);
+    state
+}
+
+#[test]
+fn test_replay_seeking() {
+    let state = State::load_replay_for_tools(Path::new("e2e-tests/almost-2024-09-27.gz"))
+        .expect("replay loaded");
+    let state = seek(Box::new(state), 1000);
+    let verification = state.verification();
+
+    let state = seek(state, 1600);
+    assert_ne!(state.verification(), verification);
+    let state = seek(state, 1000);
+    assert_eq!(state.verification().hash(), verification.hash());
+}
+
+#[test]
+fn test_unparseable_replay() {
+    let temp_dir = TempDir::new("unparseable");
+    let replay_path = tampered_replay(
+        Path::new("e2e-tests/almost-2024-09-27.gz"),
+        temp_dir.path(),
+        |lines| lines[10] = String::from("not an input"),
+    );
+    let result = State::load_replay_for_tools(&replay_path);
+    assert!(matches!(result, Err(ReplayError::Parse { line: 11, .. })));
 }
 
 #[test]
-fn test_depression_replay() {
-    let replay_path = &Path::new("e2e-tests/depression-2024-09-25.gz");
-    test_replay(replay_path);
+fn test_desynced_replay() {
+    let temp_dir = TempDir::new("desynced");
+    let replay_path = tampered_replay(
+        Path::new("e2e-tests/almost-2024-09-27.gz"),
+        temp_dir.path(),
+        |lines| break_verification_of_tick_8(lines),
+    );
+    let result = play_replay(&replay_path);
+    let desync_tick_id = match result {
+        Err(ReplayError::Desync(desync)) => match *desync {
+            Desync::Hash { tick_id, .. } => Some(tick_id),
+            Desync::Verification { .. } => None,
+        },
+        _ => None,
+    };
+    assert_eq!(desync_tick_id, Some(8));
 }
 
 #[test]
-fn test_exhaustion_replay() {
-    let replay_path = &Path::new("e2e-tests/exhaustion-2024-09-25.gz");
-    test_replay(replay_path);
+fn test_command_stream_replay() {
+    let temp_dir = TempDir::new("victory-commands");
+    std::fs::create_dir_all(temp_dir.path()).expect("replay directory created");
+    let commands_path = temp_dir.path().join("commands");
+
+    let mut state = State::load_replay_for_tools(Path::new("e2e-tests/victory-2024-10-01.gz"))
+        .expect("replay loaded");
+    state
+        .record_commands(&commands_path)
+        .expect("command recording started");
+    let inputs_result = headless::run(NoOpStore, Box::new(state), None).expect("inputs replayed");
+
+    let state = State::load_replay_for_tools(&commands_path).expect("command stream loaded");
+    let commands_result =
+        headless::run(NoOpStore, Box::new(state), None).expect("commands replayed");
+
+    assert_eq!(commands_result.side, inputs_result.side);
+    assert_eq!(commands_result.turn, inputs_result.turn);
+    assert_eq!(commands_result.player.pos, inputs_result.player.pos);
+}
+
+#[test]
+fn test_rewind_while_recording_commands() {
+    let temp_dir = TempDir::new("rewind-commands");
+    std::fs::create_dir_all(temp_dir.path()).expect("replay directory created");
+    let commands_path = temp_dir.path().join("commands");
+
+    let mut state = State::load_replay_for_tools(Path::new("e2e-tests/almost-2024-09-27.gz"))
+        .expect("replay loaded");
+    state
+        .record_commands(&commands_path)
+        .expect("command recording started");
+    let mut state = headless::run(NoOpStore, Box::new(state), None).expect("inputs replayed");
+    let (turn, pos) = (state.turn, state.player.pos);
+
+    // NOTE: the rewound ticks would get logged for the second time.
+    assert!(state.seek_replay(state.tick_id / 2).is_err());
+    let state = headless::run(NoOpStore, state, None).expect("replay finished");
+    drop(state);
+
+    let state = State::load_replay_for_tools(&commands_path).expect("command stream loaded");
+    let commands_result =
+        headless::run(NoOpStore, Box::new(state), None).expect("commands replayed");
+    assert_eq!(commands_result.turn, turn);
+    assert_eq!(commands_result.player.pos, pos);
+}
+
+/// Write the first `ticks` of a replay into `dir`. Playing the whole
+/// replay twice for every diff takes too long.
+fn replay_start(dir: &Path, ticks: usize, tamper: impl FnOnce(&mut Vec<String>)) -> PathBuf {
+    tampered_replay(Path::new("e2e-tests/almost-2024-09-27.gz"), dir, |lines| {
+        // NOTE: the inputs start on the fourth line.
+        lines.truncate(3 + ticks);
+        tamper(lines);
+    })
 }
 
 #[test]
-fn test_greedy_replay() {
-    let replay_path = &Path::new("e2e-tests/greedy-2024-09-26.gz");
-    test_replay(replay_path);
+fn test_diff_identical_replays() {
+    let temp_dir = TempDir::new("diff-identical");
+    let replay_path = replay_start(temp_dir.path(), 500, |_| {});
+    let report =
+        replay_diff::diff_replays(&replay_path, Some(&replay_path)).expect("replays diffed");
+    assert_eq!(report.replays[0].ticks, 500);
+    assert_eq!(report.replays[1].ticks, 500);
+    assert_eq!(report.first_recorded_divergence, None);
+    assert!(report.first_divergence.is_none());
 }
 
 #[test]
-fn test_overdose_replay() {
-    let replay_path = &Path::new("e2e-tests/overdose-2024-09-25.gz");
-    test_replay(replay_path);
+fn test_diff_tampered_verification() {
+    let temp_dir = TempDir::new("diff-tampered");
+    let replay_path = replay_start(&temp_dir.path().join("original"), 500, |_| {});
+    let tampered_path = replay_start(&temp_dir.path().join("tampered"), 500, |lines| {
+        break_verification_of_tick_8(lines)
+    });
+    let report =
+        replay_diff::diff_replays(&replay_path, Some(&tampered_path)).expect("replays diffed");
+    assert_eq!(report.first_recorded_divergence, Some(8));
+    // NOTE: only the recording changed, the game plays the same.
+    assert!(report.first_divergence.is_none());
+    assert!(report.replays[1].desync.is_some());
 }
 
 #[test]
-fn test_stunned_replay() {
-    let replay_path = &Path::new("e2e-tests/stunned-2024-09-25.gz");
-    test_replay(replay_path);
+fn test_diff_truncated_replay() {
+    let temp_dir = TempDir::new("diff-truncated");
+    let replay_path = replay_start(&temp_dir.path().join("original"), 500, |_| {});
+    let truncated_path = replay_start(&temp_dir.path().join("truncated"), 100, |_| {});
+    let report =
+        replay_diff::diff_replays(&replay_path, Some(&truncated_path)).expect("replays diffed");
+    assert_eq!(report.replays[1].ticks, 100);
+    assert_eq!(report.first_recorded_divergence, Some(101));
+    let divergence = report.first_divergence.expect("the replays diverge");
+    assert_eq!(divergence.tick_id, 101);
+    assert!(divergence.diff.is_none());
 }
 
 #[test]
-fn test_victory_replay() {
-    let replay_path = &Path::new("e2e-tests/victory-2024-10-01.gz");
-    test_replay(replay_path);
+fn test_minimise_desynced_replay() {
+    let temp_dir = TempDir::new("minimise-desynced");
+    let replay_path = tampered_replay(
+        Path::new("e2e-tests/almost-2024-09-27.gz"),
+        temp_dir.path(),
+        |lines| break_verification_of_tick_8(lines),
+    );
+    let output_path = temp_dir.path().join("minimised");
+    let summary = replay_minimise::minimise(&replay_path, Failure::Desync, &output_path, 100)
+        .expect("replay minimised");
+    assert!(summary.minimised_inputs < summary.original_inputs);
+    assert!(matches!(
+        play_replay(&output_path),
+        Err(ReplayError::Desync(_))
+    ));
 }
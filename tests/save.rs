@@ -0,0 +1,218 @@
+#![allow(clippy::expect_used)]
+
+mod common;
+
+use common::TempDir;
+use dose_response::{
+    engine::Mouse,
+    generators::Landscape,
+    inspect,
+    palette::Palette,
+    point::Point,
+    save::{self, Slots},
+    session::GameSession,
+    state::{
+        self, Challenge, Command, DisplaySettings, Input, ReplayStream, State, VerificationWrapper,
+    },
+    window::Window,
+};
+
+#[test]
+fn test_save_slots() {
+    let temp_dir = TempDir::new("saves");
+    let dir = temp_dir.path();
+    let slots = Slots::new(dir);
+    assert!(slots
+        .list()
+        .expect("Listing a missing directory")
+        .is_empty());
+
+    let mut session = GameSession::new(7, Challenge::default());
+    session.step(Command::N);
+    session.step(Command::E);
+    let state = session.state();
+
+    let name = slots.next_free_name();
+    assert_eq!(name, "slot-1");
+    slots.save(&name, state).expect("Saving the game");
+    slots.save("before-boss", state).expect("Saving the game");
+    assert_eq!(slots.next_free_name(), "slot-2");
+    assert!(slots.save("../escape", state).is_err());
+
+    let listed = slots.list().expect("Listing the saves");
+    assert_eq!(listed.len(), 2);
+    let slot = listed
+        .iter()
+        .find(|slot| slot.metadata.name == "slot-1")
+        .expect("The saved slot is listed");
+    assert_eq!(slot.metadata.turn, state.turn);
+    assert_eq!(slot.metadata.seed, 7);
+    assert_eq!(slot.metadata.will, state.player.will.to_int());
+    assert_eq!(slot.metadata.challenge, Challenge::default());
+
+    let loaded = save::load(&slot.path).expect("Loading the game");
+    assert_eq!(loaded.turn, state.turn);
+    assert_eq!(loaded.player.pos, state.player.pos);
+    assert!(!slot.path.exists(), "Loading a save deletes it");
+    assert_eq!(slots.list().expect("Listing the saves").len(), 1);
+}
+
+#[test]
+fn test_save_format() {
+    let temp_dir = TempDir::new("format");
+    let dir = temp_dir.path();
+    std::fs::create_dir_all(dir).expect("Creating the save directory");
+    let slots = Slots::new(dir);
+
+    let mut session = GameSession::new(11, Challenge::default());
+    session.step(Command::W);
+    let state = session.state();
+
+    // A single flipped byte is caught by the checksum:
+    let path = slots.save("corrupted", state).expect("Saving the game");
+    let mut contents = std::fs::read(&path).expect("Reading the save");
+    let last = contents.len() - 1;
+    contents[last] ^= 0xff;
+    std::fs::write(&path, &contents).expect("Writing the save");
+    assert!(save::load(&path).is_err());
+    assert!(path.exists(), "A save that didn't load is kept");
+
+    // The saves from before the format existed are migrated. The
+    // fixture was written by the game before the save format, run
+    // stats, command stream, landscapes and regions:
+    let legacy_path = dir.join("legacy.sav");
+    std::fs::copy("tests/fixtures/SAVEDGAME.sav", &legacy_path).expect("Copying the legacy save");
+
+    let header = save::read_header(&legacy_path).expect("Reading the legacy header");
+    assert_eq!(header.version, "2.0.0-dev");
+    assert_eq!(header.commit, "68d23d547d85364f781c32ee1cbe17e066fcbc27");
+    assert_eq!(header.metadata.name, "legacy");
+    assert_eq!(header.metadata.turn, 42);
+    assert_eq!(header.replay_path, None);
+    let loaded = save::load(&legacy_path).expect("Loading the legacy save");
+    assert_eq!(loaded.turn, 42);
+    assert_eq!(loaded.player.tolerance, 3);
+    assert_eq!(loaded.player.pos, Point::new(0, 0));
+    assert_eq!(loaded.player.run_stats, Default::default());
+    assert_eq!(loaded.commands_executed, 0);
+    assert!(matches!(
+        loaded.window_stack.top(),
+        Window::Message { ref title, .. } if title == "Saved"
+    ));
+    assert!(!loaded.world.positions_of_all_chunks().is_empty());
+    assert_eq!(loaded.world.landscape(), Landscape::Forest);
+    assert!(!loaded.world.regions());
+}
+
+#[test]
+fn test_autosave_and_session() {
+    let temp_dir = TempDir::new("autosave");
+    let dir = temp_dir.path();
+    let slots = Slots::new(dir);
+
+    assert!(!slots.begin_session().expect("Beginning the session"));
+    assert!(
+        slots.begin_session().expect("Beginning the session"),
+        "The previous session didn't end"
+    );
+    assert!(slots.latest_autosave().is_none());
+
+    let mut session = GameSession::new(3, Challenge::default());
+    let mut paths = vec![];
+    for _ in 0..(save::AUTOSAVE_SLOTS + 1) {
+        session.step(Command::S);
+        paths.push(slots.autosave(session.state()).expect("Autosaving"));
+        // NOTE: make sure the files get different modification times.
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    assert_eq!(
+        paths[0],
+        paths[save::AUTOSAVE_SLOTS as usize],
+        "The slots rotate"
+    );
+    let latest = slots.latest_autosave().expect("The latest autosave");
+    assert_eq!(latest.path, paths[save::AUTOSAVE_SLOTS as usize]);
+    assert_eq!(latest.metadata.turn, session.state().turn);
+    assert!(
+        slots.list().expect("Listing the saves").is_empty(),
+        "Autosaves aren't listed with the saves"
+    );
+
+    slots.end_session();
+    assert!(slots.latest_autosave().is_none());
+    assert!(!slots.begin_session().expect("Beginning the session"));
+    slots.end_session();
+}
+
+#[test]
+fn test_save_continues_replay() {
+    let temp_dir = TempDir::new("replay");
+    let dir = temp_dir.path();
+    std::fs::create_dir_all(dir).expect("Creating the save directory");
+    let slots = Slots::new(dir);
+    let replay_path = dir.join("replay");
+
+    let mut state = State::new_game(
+        dose_response::WORLD_SIZE,
+        Point::from_i32(dose_response::DISPLAYED_MAP_SIZE),
+        dose_response::PANEL_WIDTH,
+        false,
+        false,
+        false,
+        Some(replay_path.clone()),
+        ReplayStream::Inputs,
+        7,
+        Challenge::default(),
+        Landscape::default(),
+        Palette::classic(),
+        DisplaySettings::default(),
+    );
+    state.generate_world();
+    let path = slots.save("replayed", &state).expect("Saving the game");
+    let header = save::read_header(&path).expect("Reading the header");
+    assert_eq!(
+        header.replay_path,
+        Some(std::fs::canonicalize(&replay_path).expect("The replay exists"))
+    );
+
+    let mut loaded = save::load(&path).expect("Loading the game");
+    let input = Input {
+        keys: vec![],
+        mouse: Mouse::new(),
+        tick_id: 1,
+        verification: VerificationWrapper::None,
+    };
+    state::log_input(&mut loaded.input_logger, input);
+    let replay = std::fs::read_to_string(&replay_path).expect("Reading the replay");
+    assert_eq!(replay.lines().count(), 2, "The input follows the header");
+}
+
+#[test]
+fn test_inspect_save() {
+    let temp_dir = TempDir::new("inspect");
+    let dir = temp_dir.path();
+    let slots = Slots::new(dir);
+
+    let mut session = GameSession::new(11, Challenge::default());
+    session.step(Command::W);
+    let state = session.state();
+    let path = slots.save("inspected", state).expect("Saving the game");
+
+    let inspection = inspect::inspect(&path).expect("Inspecting the save");
+    let json = serde_json::to_value(&inspection).expect("Serialising the inspection");
+    let save = &json["save"];
+    assert_eq!(save["header"]["metadata"]["seed"], 11);
+    assert_eq!(save["turn"], state.turn);
+    assert_eq!(save["player"]["will"], state.player.will.to_int());
+    assert_eq!(save["player"]["pos"]["x"], state.player.pos.x);
+    assert_eq!(
+        save["chunks"].as_array().map(Vec::len),
+        Some(state.world.positions_of_all_chunks().len())
+    );
+    assert!(save["monsters"].is_object());
+    assert!(!save["window_stack"]
+        .as_array()
+        .expect("The window stack")
+        .is_empty());
+    assert!(path.exists(), "Inspecting a save doesn't delete it");
+}
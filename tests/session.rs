@@ -0,0 +1,269 @@
+#![allow(clippy::expect_used)]
+
+use dose_response::{
+    agent_protocol, autoplay,
+    balance::{self, Policy},
+    generators::{Landscape, Region},
+    item,
+    level::TileKind,
+    map_export::{Layer, Map},
+    player::Modifier,
+    point::Point,
+    random::Random,
+    rect::Rectangle,
+    session::{GameSession, Outcome},
+    share_code::ShareCode,
+    state::{Challenge, Command, State},
+};
+
+fn play(seed: u32, steps: usize) -> GameSession {
+    let commands = [
+        Command::N,
+        Command::NE,
+        Command::E,
+        Command::SE,
+        Command::S,
+        Command::SW,
+        Command::W,
+        Command::NW,
+        Command::UseFood,
+    ];
+    let mut session = GameSession::new(seed, Challenge::default());
+    for index in 0..steps {
+        let command = commands[(index * 7 + index / 3) % commands.len()].clone();
+        if session.step(command).outcome != Outcome::Running {
+            break;
+        }
+    }
+    session
+}
+
+#[test]
+fn test_step_advances_the_game() {
+    let mut session = GameSession::new(1, Challenge::default());
+    let start = session.observation();
+    assert_eq!(start.turn, 0);
+
+    let result = session.step(Command::E);
+    let observation = session.observation();
+    if result.acted {
+        assert_eq!(observation.turn, 1);
+        assert!(observation.action_points > 0);
+    } else {
+        assert_eq!(observation.player_pos, start.player_pos);
+    }
+}
+
+#[test]
+fn test_same_seed_same_game() {
+    let first = play(42, 300);
+    let second = play(42, 300);
+    assert_eq!(first.observation(), second.observation());
+    assert_eq!(first.state().verification(), second.state().verification());
+}
+
+#[test]
+fn test_agent_protocol() {
+    let session = GameSession::new(1, Challenge::default());
+    let input = "\"N\"\nnot a command\n";
+    let mut output = vec![];
+    agent_protocol::run(session, input.as_bytes(), &mut output).expect("protocol finished");
+
+    let responses: Vec<serde_json::Value> = String::from_utf8_lossy(&output)
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("response parsed"))
+        .collect();
+    assert_eq!(responses.len(), 3);
+    assert!(responses[0]["result"].is_null());
+    assert!(responses[1]["result"].is_object());
+    assert!(responses[2]["error"].is_string());
+    assert_eq!(responses[2]["observation"], responses[1]["observation"]);
+}
+
+#[test]
+fn test_autoplay_plays_to_the_end() {
+    let play = |seed| {
+        let mut session = GameSession::new(seed, Challenge::default());
+        session
+            .play(autoplay::next_command)
+            .expect("the bot finished the game");
+        session
+    };
+    for seed in [1, 2, 3] {
+        let first = play(seed);
+        let second = play(seed);
+        assert_ne!(first.outcome(), Outcome::Running);
+        assert_eq!(first.state().verification(), second.state().verification());
+    }
+}
+
+#[test]
+fn test_balance_summary() {
+    let challenge = Challenge::default();
+    let games = balance::run(Policy::RandomWalk, challenge, 10, 4);
+    let seeds: Vec<u32> = games.iter().map(|game| game.report.seed).collect();
+    assert_eq!(seeds, vec![10, 11, 12, 13]);
+
+    let summary = balance::Summary::new(Policy::RandomWalk, challenge, 10, &games);
+    assert_eq!(summary.games, 4);
+    let deaths: usize = summary.causes_of_death.values().sum();
+    assert_eq!(deaths + summary.victories + summary.stuck, 4);
+    assert!(f64::from(summary.turns.min) <= summary.turns.median);
+    assert!(summary.turns.median <= f64::from(summary.turns.max));
+}
+
+#[test]
+fn test_urban_landscape() {
+    let session = GameSession::with_landscape(5, Challenge::default(), Landscape::Urban);
+    let world = &session.state().world;
+    assert_eq!(world.landscape(), Landscape::Urban);
+
+    // The player starts on a street that runs both ways:
+    for offset in 0..32 {
+        for pos in [Point::new(offset, 0), Point::new(0, offset)] {
+            let cell = world.cell(pos).expect("The starting chunk exists");
+            assert_eq!(cell.tile.kind, TileKind::Empty, "{} is on a street", pos);
+        }
+    }
+    let walls = (3..16)
+        .flat_map(|x| (3..16).map(move |y| Point::new(x, y)))
+        .filter(|&pos| {
+            world
+                .cell(pos)
+                .is_some_and(|cell| cell.tile.kind != TileKind::Empty)
+        })
+        .count();
+    assert!(walls > 0, "The first block has a building or a park");
+
+    let again = GameSession::with_landscape(5, Challenge::default(), Landscape::Urban);
+    assert_eq!(again.observation(), session.observation());
+}
+
+#[test]
+fn test_regions() {
+    let start = Region::new(3, Point::new(16, 16));
+    assert_eq!(start.difficulty, 0.0);
+    let far = Region::new(3, Point::new(1000, -1000));
+    assert_eq!(far.difficulty, 1.0);
+
+    // The biome changes slowly across the world:
+    let biomes: Vec<f32> = (0..64)
+        .map(|chunk| Region::new(3, Point::new(chunk * 32 + 16, 16)).biome)
+        .collect();
+    assert!(biomes.iter().all(|biome| (-1.0..=1.0).contains(biome)));
+    assert!(biomes
+        .windows(2)
+        .all(|pair| (pair[0] - pair[1]).abs() < 0.5));
+    assert!(biomes.iter().any(|&biome| biome != biomes[0]));
+
+    // The harder regions have more monsters and stronger doses:
+    let generate = |seed, region| {
+        let mut rng = Random::from_seed(seed);
+        let mut throwaway_rng = Random::from_seed(seed);
+        Landscape::Forest.generator().generate(
+            &mut rng,
+            &mut throwaway_rng,
+            Point::new(32, 32),
+            Point::new(0, 0),
+            Challenge::default(),
+            region,
+        )
+    };
+    let hardest = Region {
+        difficulty: 1.0,
+        biome: 0.0,
+    };
+    let count_monsters = |region| {
+        (0..20)
+            .map(|seed| generate(seed, region).1.len())
+            .sum::<usize>()
+    };
+    assert!(count_monsters(hardest) > count_monsters(Region::default()));
+    let dose_minds = |region| {
+        (0..20)
+            .flat_map(|seed| generate(seed, region).2)
+            .filter_map(|(_, item)| match item.modifier {
+                Modifier::Intoxication { state_of_mind, .. } if item.kind == item::Kind::Dose => {
+                    Some(state_of_mind)
+                }
+                _ => None,
+            })
+            .sum::<i32>()
+    };
+    assert!(dose_minds(hardest) > dose_minds(Region::default()));
+}
+
+#[test]
+fn test_share_code() {
+    let code = ShareCode {
+        seed: 3_000_000_123,
+        challenge: Challenge {
+            hide_unseen_tiles: false,
+            fast_depression: true,
+            one_chance: false,
+        },
+        landscape: Landscape::Urban,
+    };
+    let text = code.to_string();
+    assert_eq!(text.len(), 11);
+    assert_eq!(text.parse::<ShareCode>().expect("valid code"), code);
+    let sloppy = format!(" {} ", text.replace('-', "").to_lowercase());
+    assert_eq!(sloppy.parse::<ShareCode>().expect("valid code"), code);
+
+    // A typo is caught by the checksum:
+    let mut typo = text.into_bytes();
+    typo[2] = if typo[2] == b'X' { b'Y' } else { b'X' };
+    let typo = String::from_utf8(typo).expect("ASCII");
+    assert!(typo.parse::<ShareCode>().is_err());
+    assert!("ABC".parse::<ShareCode>().is_err());
+
+    // A plain number keeps the passed challenge and landscape:
+    let plain = ShareCode::from_seed_text("42", Challenge::default(), Landscape::Forest)
+        .expect("a number is a valid seed");
+    assert_eq!(plain.seed, 42);
+    assert_eq!(plain.challenge, Challenge::default());
+    let shared =
+        ShareCode::from_seed_text(&code.to_string(), Challenge::default(), Landscape::Forest)
+            .expect("a share code is a valid seed");
+    assert_eq!(shared, code);
+
+    let date = |day| chrono::NaiveDate::from_ymd_opt(2024, 5, day).expect("valid date");
+    assert_eq!(ShareCode::daily(date(1)), ShareCode::daily(date(1)));
+    assert_ne!(
+        ShareCode::daily(date(1)).seed,
+        ShareCode::daily(date(2)).seed
+    );
+}
+
+#[test]
+fn test_map_export() {
+    let mut state = State::new_session(9, Challenge::default(), Landscape::Forest);
+    let player = state.player.clone();
+    let palette = state.palette;
+
+    // Far away from the start so the chunks have to be generated:
+    let far = Rectangle::center(Point::new(500, -300), Point::from_i32(20));
+    let map = Map::new(&mut state.world, Some(&player), &palette, far, false);
+    assert!(map.cells.iter().all(|cell| cell.layer != Layer::Unexplored));
+    assert!(map.cells.iter().any(|cell| cell.layer == Layer::Tree));
+    let text = map.to_text();
+    assert_eq!(text.lines().count(), 41);
+    assert!(text.lines().all(|line| line.chars().count() <= 41));
+
+    let around_player = Rectangle::center(player.pos, Point::from_i32(40));
+    let map = Map::new(
+        &mut state.world,
+        Some(&player),
+        &palette,
+        around_player,
+        true,
+    );
+    let cell = map.cell(player.pos).expect("The player is on the map");
+    assert_eq!(cell.layer, Layer::Player);
+    assert_eq!(cell.glyph, '@');
+    // Only the area around the player has been explored:
+    assert_eq!(
+        map.cell(player.pos + (40, 40)).map(|cell| cell.layer),
+        Some(Layer::Unexplored)
+    );
+}
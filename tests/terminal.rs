@@ -0,0 +1,46 @@
+#![cfg(all(feature = "terminal-backend", unix))]
+
+use dose_response::{
+    engine::terminal::{self, Renderer},
+    keys::{Key, KeyCode},
+    point::Point,
+    settings::NoOpStore,
+    state::{Challenge, State},
+};
+
+use std::time::Duration;
+
+#[test]
+fn test_terminal_keys() {
+    let keys = terminal::parse_keys(b"\x1b[A\x1b[1;2Dk?\x1b[5~\r\x7f\x1b");
+    assert_eq!(
+        keys,
+        vec![
+            KeyCode::Up.into(),
+            Key {
+                shift: true,
+                ..KeyCode::Left.into()
+            },
+            KeyCode::K.into(),
+            KeyCode::QuestionMark.into(),
+            KeyCode::NumPad9.into(),
+            KeyCode::Enter.into(),
+            KeyCode::Backspace.into(),
+            KeyCode::Esc.into(),
+        ]
+    );
+}
+
+#[test]
+fn test_terminal_screenshot() {
+    let mut state = State::new_session(1, Challenge::default(), Default::default());
+    let mut renderer = Renderer::new(NoOpStore, Point::new(100, 40));
+    for _ in 0..3 {
+        renderer.update(&mut state, Duration::from_millis(16), &[]);
+    }
+    let screenshot = renderer.screen().to_plain_text();
+
+    assert_eq!(screenshot.lines().count(), 40);
+    assert!(screenshot.contains('@'), "{}", screenshot);
+    assert!(screenshot.contains("Will: "), "{}", screenshot);
+}